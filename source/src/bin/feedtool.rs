@@ -0,0 +1,166 @@
+/*
+    feedtool csv-to-bin <csv> <binary>
+    feedtool c-to-csv <c-file> <csv>
+    feedtool csv-to-c <csv> <c-file> [--header]
+    feedtool check <feed> <feed>
+    feedtool generate <csv> [--seed <n>] [--messages <n>] [--symbols <n>] [--traders <n>] [--mid <price>]
+                            [--volatility <p>] [--cancel-ratio <p>] [--market-ratio <p>] [--aggressiveness <p>]
+                            [--depth <levels>] [--spacing <ticks>] [--sweep-ratio <p>] [--sweep-size <size>]
+                            [--target-depth <orders>] [--lot <size>] [--profile <name>] [--legacy]
+
+    Conversions between the feed formats under src/feed, and synthetic feeds from src/feed/generator.rs.
+    C files hold the initialiser lines of cpp_reference (parsed_feed.txt, or score_feed.h with --header).
+    check compares two feeds in either format (.h and .txt files are read as C) and exits 1 if they differ.
+    generate writes a v2 feed, or with --legacy the original feed format (which cannot hold market orders).
+    --profile starts from one of the benchmark profiles in src/score/profiles.rs, later flags override it.
+*/
+
+use std::env;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::process;
+use std::str::FromStr;
+use high_frequency_order_book::feed::binary::convert_csv;
+use high_frequency_order_book::feed::commands::write_commands;
+use high_frequency_order_book::feed::feed::{load_feed, to_legacy, write_feed, FeedError, Mode};
+use high_frequency_order_book::feed::initialiser::{compare, load_initialisers, write_initialisers, Layout};
+use high_frequency_order_book::types::Order;
+use high_frequency_order_book::feed::generator::{generate, GeneratorConfig, SizeDistribution};
+use high_frequency_order_book::score::profiles::profile;
+
+const USAGE: &str = "usage: feedtool csv-to-bin <csv> <binary>\n       feedtool c-to-csv <c-file> <csv>\n       \
+feedtool csv-to-c <csv> <c-file> [--header]\n       feedtool check <feed> <feed>\n       feedtool generate <csv> [--seed <n>] [--messages <n>] [--symbols <n>] [--traders <n>] [--mid <price>] \
+[--volatility <p>] [--cancel-ratio <p>] [--market-ratio <p>] [--aggressiveness <p>] [--depth <levels>] [--spacing <ticks>] \
+[--sweep-ratio <p>] [--sweep-size <size>] [--target-depth <orders>] [--lot <size>] [--profile <name>] [--legacy]";
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(2);
+}
+
+// Differences printed by check before it only counts them.
+const SHOWN: usize = 10;
+
+fn load(path: &str) -> Vec<Order> {
+    let is_c = matches!(Path::new(path).extension().and_then(|x| x.to_str()), Some("h") | Some("txt"));
+    let loaded: Result<_, FeedError> = if is_c { load_initialisers(path, Mode::Strict) } else { load_feed(path, Mode::Strict) };
+    loaded.unwrap_or_else(|e| fail(&e.to_string())).flow
+}
+
+fn run_check(left: &str, right: &str) {
+    let (left_flow, right_flow) = (load(left), load(right));
+    let differences = compare(&left_flow, &right_flow);
+    if differences.is_empty() {
+        println!("{} and {} agree on {} orders", left, right, left_flow.len());
+        return;
+    }
+
+    for difference in differences.iter().take(SHOWN) {
+        println!("{}", difference);
+    }
+    if differences.len() > SHOWN {
+        println!("... {} more", differences.len() - SHOWN);
+    }
+    eprintln!("{} ({} orders) and {} ({} orders) differ at {} positions", left, left_flow.len(), right, right_flow.len(), differences.len());
+    process::exit(1);
+}
+
+fn parse<T: FromStr>(flag: &str, value: Option<&String>) -> T {
+    value.and_then(|x| x.parse().ok()).unwrap_or_else(|| fail(&format!("{} takes a number", flag)))
+}
+
+fn probability(flag: &str, value: Option<&String>) -> f64 {
+    let p: f64 = parse(flag, value);
+    if !(0.0..=1.0).contains(&p) {
+        fail(&format!("{} takes a probability between 0 and 1", flag));
+    }
+    p
+}
+
+fn run_generate(output: &str, flags: &[String]) {
+    let mut config = GeneratorConfig::new(0);
+    let mut legacy = false;
+
+    let mut rest = flags.iter();
+    while let Some(flag) = rest.next() {
+        match flag.as_str() {
+            "--legacy" => legacy = true,
+            "--seed" => config.seed = parse(flag, rest.next()),
+            "--messages" => config.messages = parse(flag, rest.next()),
+            "--symbols" => config.symbols = parse(flag, rest.next()),
+            "--traders" => config.traders = parse(flag, rest.next()),
+            "--mid" => config.mid = parse(flag, rest.next()),
+            "--depth" => config.depth = parse(flag, rest.next()),
+            "--target-depth" => config.target_depth = parse(flag, rest.next()),
+            "--spacing" => config.spacing = parse(flag, rest.next()),
+            "--sweep-size" => config.sweep_size = parse(flag, rest.next()),
+            "--sweep-ratio" => config.sweep_ratio = probability(flag, rest.next()),
+            "--profile" => {
+                let name = rest.next().unwrap_or_else(|| fail(USAGE));
+                config = profile(name, config.seed).unwrap_or_else(|| fail(&format!("unknown profile '{}'", name))).config;
+            }
+            "--lot" => match config.sizes {
+                SizeDistribution::Geometric { p, .. } => config.sizes = SizeDistribution::Geometric { lot: parse(flag, rest.next()), p },
+                _ => fail("--lot only applies to geometric order sizes, which this profile does not use"),
+            },
+            "--volatility" => config.volatility = probability(flag, rest.next()),
+            "--cancel-ratio" => config.cancel_ratio = probability(flag, rest.next()),
+            "--market-ratio" => config.market_ratio = probability(flag, rest.next()),
+            "--aggressiveness" => config.aggressiveness = probability(flag, rest.next()),
+            _ => fail(USAGE),
+        }
+    }
+    if legacy && config.market_ratio > 0.0 {
+        fail("--legacy feeds cannot hold market orders, use --market-ratio 0");
+    }
+
+    let commands = generate(&config).unwrap_or_else(|e| fail(&e.to_string()));
+    let writer = BufWriter::new(File::create(output).unwrap_or_else(|e| fail(&format!("{}: {}", output, e))));
+    let written = if legacy {
+        let flow: Vec<_> = commands.iter().filter_map(to_legacy).collect();
+        write_feed(writer, &flow)
+    } else {
+        write_commands(writer, &commands)
+    };
+
+    match written {
+        Ok(()) => println!("wrote {} messages to {} (seed {})", commands.len(), output, config.seed),
+        Err(e) => fail(&format!("{}: {}", output, e)),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    match args.iter().map(|x| x.as_str()).collect::<Vec<&str>>().as_slice() {
+        ["csv-to-bin", input, output] => match convert_csv(input, output) {
+            Ok(records) => println!("wrote {} records to {}", records, output),
+            Err(e) => fail(&format!("{}: {}", input, e)),
+        },
+        ["c-to-csv", input, output] => {
+            let flow = load_initialisers(input, Mode::Strict).unwrap_or_else(|e| fail(&e.to_string())).flow;
+            let file = File::create(output).unwrap_or_else(|e| fail(&format!("{}: {}", output, e)));
+            match write_feed(BufWriter::new(file), &flow) {
+                Ok(()) => println!("wrote {} orders to {}", flow.len(), output),
+                Err(e) => fail(&format!("{}: {}", output, e)),
+            }
+        }
+        ["csv-to-c", input, output, flags @ ..] => {
+            let layout = match flags {
+                [] => Layout::Lines,
+                ["--header"] => Layout::Header,
+                _ => fail(USAGE),
+            };
+            let flow = load_feed(input, Mode::Strict).unwrap_or_else(|e| fail(&e.to_string())).flow;
+            let file = File::create(output).unwrap_or_else(|e| fail(&format!("{}: {}", output, e)));
+            match write_initialisers(file, &flow, layout) {
+                Ok(()) => println!("wrote {} orders to {}", flow.len(), output),
+                Err(e) => fail(&format!("{}: {}", output, e)),
+            }
+        }
+        ["check", left, right] => run_check(left, right),
+        ["generate", output, ..] => run_generate(output, &args[2..]),
+        _ => fail(USAGE),
+    }
+}
@@ -0,0 +1,91 @@
+/*
+    replay record <digest-file> [--feed <path> | --journal <path> | --itch <path> [--stock <symbol>]] [--every <n>]
+    replay verify <digest-file> [--feed <path> | --journal <path> | --itch <path> [--stock <symbol>]] [--every <n>]
+
+    Replays score_feed.csv (or another feed, or a command journal) through engine.rs and records or checks the
+    output and book digests, see src/replay/replay.rs. --itch reads an ITCH capture, keeping only --stock if given.
+*/
+
+use std::env;
+use std::process;
+use high_frequency_order_book::engine::command::Command;
+use high_frequency_order_book::feed::feed::{get_raw_feed, to_command, Mode};
+use high_frequency_order_book::feed::commands::load_commands;
+use high_frequency_order_book::feed::itch::{load_capture, ItchConfig};
+use high_frequency_order_book::journal::journal::Journal;
+use high_frequency_order_book::replay::replay::{replay, verify, read_digests, write_digests};
+
+const USAGE: &str = "usage: replay <record|verify> <digest-file> [--feed <path> | --journal <path> | --itch <path> [--stock <symbol>]] [--every <n>]";
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(2);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.len() < 2 {
+        fail(USAGE);
+    }
+    let (mode, digest_path) = (&args[0], &args[1]);
+
+    let mut feed = None;
+    let mut journal = None;
+    let mut itch = None;
+    let mut config = ItchConfig::new();
+    let mut every = 1000;
+    let mut rest = args[2..].iter();
+    while let Some(flag) = rest.next() {
+        let value = rest.next().unwrap_or_else(|| fail(USAGE));
+        match flag.as_str() {
+            "--feed" => feed = Some(value.clone()),
+            "--journal" => journal = Some(value.clone()),
+            "--itch" => itch = Some(value.clone()),
+            "--stock" => config.stock = Some(value.clone()),
+            "--every" => every = value.parse().ok().filter(|n| *n > 0).unwrap_or_else(|| fail("--every takes a positive number")),
+            _ => fail(USAGE),
+        }
+    }
+
+    let commands: Vec<Command> = match (feed, journal, itch) {
+        (Some(path), None, None) => match load_commands(&path, Mode::Strict) {
+            Ok(feed) => feed.commands,
+            Err(e) => fail(&e.to_string()),
+        },
+        (None, Some(path), None) => match Journal::read(&path) {
+            Ok(records) => records.into_iter().map(|x| x.command).collect(),
+            Err(e) => fail(&format!("{}: {}", path, e)),
+        },
+        (None, None, Some(path)) => match load_capture(&path, &config) {
+            Ok(commands) => commands,
+            Err(e) => fail(&format!("{}: {}", path, e)),
+        },
+        (None, None, None) => match get_raw_feed() {
+            Ok(flow) => flow.iter().map(to_command).collect(),
+            Err(e) => fail(&e.to_string()),
+        },
+        _ => fail(USAGE),
+    };
+
+    let checkpoints = replay(commands, every);
+
+    match mode.as_str() {
+        "record" => {
+            if let Err(e) = write_digests(digest_path, &checkpoints) {
+                fail(&format!("{}: {}", digest_path, e));
+            }
+            println!("recorded {} checkpoints to {}", checkpoints.len(), digest_path);
+        }
+        "verify" => {
+            let expected = read_digests(digest_path).unwrap_or_else(|e| fail(&format!("{}: {}", digest_path, e)));
+            match verify(&expected, &checkpoints) {
+                Ok(()) => println!("identical over {} checkpoints", checkpoints.len()),
+                Err(divergence) => {
+                    eprintln!("{}", divergence);
+                    process::exit(1);
+                }
+            }
+        }
+        _ => fail(USAGE),
+    }
+}
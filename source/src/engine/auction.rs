@@ -0,0 +1,72 @@
+/*
+    Equilibrium price determination for call auctions.
+*/
+
+use crate::types::{Price, Size};
+use crate::engine::engine::OrderIn;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Indicative {
+    pub price: Price,
+    // Quantity that would trade at price.
+    pub volume: Size,
+    // Bid quantity minus ask quantity executable at price, positive when buyers are left over.
+    pub surplus: i128,
+}
+
+fn at_price(bids: &[OrderIn], asks: &[OrderIn], price: Price) -> Indicative {
+    let demand: Size = bids.iter().take_while(|x| x.order.price >= price).map(|x| x.order.size).sum();
+    let supply: Size = asks.iter().take_while(|x| x.order.price <= price).map(|x| x.order.size).sum();
+
+    Indicative {
+        price,
+        volume: demand.min(supply),
+        surplus: demand as i128 - supply as i128,
+    }
+}
+
+// Finds the single price maximising executed volume when the book is uncrossed.
+// bids and asks must be in priority order (best price first). Ties are broken by, in order,
+// the smallest surplus, market pressure (highest price when every candidate has buyers left over,
+// lowest when every candidate has sellers left over) and closeness to the reference price
+// (or to the middle of the candidate range when there is none).
+// Returns None when the book does not cross.
+pub fn equilibrium(bids: &[OrderIn], asks: &[OrderIn], reference: Option<Price>) -> Option<Indicative> {
+    let (best_bid, best_ask) = match (bids.first(), asks.first()) {
+        (Some(b), Some(a)) => (b.order.price, a.order.price),
+        _ => return None,
+    };
+    if best_bid < best_ask {
+        return None;
+    }
+
+    // Volume only changes at order prices, so those are the only candidates worth testing.
+    let mut prices: Vec<Price> = bids.iter().chain(asks.iter())
+        .map(|x| x.order.price)
+        .filter(|p| *p >= best_ask && *p <= best_bid)
+        .collect();
+    prices.sort_unstable();
+    prices.dedup();
+
+    let mut candidates: Vec<Indicative> = prices.into_iter().map(|p| at_price(bids, asks, p)).collect();
+
+    let volume = candidates.iter().map(|x| x.volume).max()?;
+    candidates.retain(|x| x.volume == volume);
+
+    let surplus = candidates.iter().map(|x| x.surplus.abs()).min()?;
+    candidates.retain(|x| x.surplus.abs() == surplus);
+
+    if candidates.iter().all(|x| x.surplus > 0) {
+        return candidates.last().copied();
+    }
+    if candidates.iter().all(|x| x.surplus < 0) {
+        return candidates.first().copied();
+    }
+
+    let low = candidates.first()?.price;
+    let high = candidates.last()?.price;
+    let target = reference.unwrap_or(low + (high - low) / 2);
+
+    // Lowest price wins when two candidates are equally close.
+    candidates.into_iter().min_by_key(|x| (x.price as i32 - target as i32).abs())
+}
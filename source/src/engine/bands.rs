@@ -0,0 +1,30 @@
+/*
+    Price collars protecting the book from orders and sweeps far away from the market.
+*/
+
+use serde::{Serialize, Deserialize};
+use crate::types::Price;
+
+// Widths are in ticks either side of the reference, None disables the collar.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PriceBands {
+    // Limit orders priced further than this from the reference price are rejected.
+    pub static_band: Option<Price>,
+    // A sweep that would trade further than this from the last trade price stops and halts the market.
+    pub dynamic_band: Option<Price>,
+}
+
+impl PriceBands {
+
+    pub fn new() -> PriceBands {
+        PriceBands { static_band: None, dynamic_band: None }
+    }
+
+    // True when there is no collar or no reference to measure against.
+    pub fn within(band: Option<Price>, reference: Option<Price>, price: Price) -> bool {
+        match (band, reference) {
+            (Some(width), Some(reference)) => price.abs_diff(reference) <= width,
+            _ => true,
+        }
+    }
+}
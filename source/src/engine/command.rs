@@ -0,0 +1,54 @@
+/*
+    Every inbound request the engine understands, as data so it can be journaled and replayed.
+*/
+
+use serde::{Serialize, Deserialize};
+use crate::types::{Order, OrderId, Price, Size};
+use crate::engine::engine::Engine;
+use crate::engine::auction::Indicative;
+use crate::engine::session::{Phase, Reject, InvalidTransition};
+use crate::engine::bands::PriceBands;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Command {
+    Limit(Order),
+    Market(Order),
+    Cancel(OrderId),
+    Replace { id: OrderId, order: Order },
+    Amend { id: OrderId, size: Size },
+    Transition(Phase),
+    SetReferencePrice(Option<Price>),
+    SetPriceBands(PriceBands),
+}
+
+// What the engine answered to a command.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Applied {
+    Order(Result<OrderId, Reject>),
+    Cancel(Result<(), Reject>),
+    Amend(Result<(), Reject>),
+    Transition(Result<Option<Indicative>, InvalidTransition>),
+    Configured,
+}
+
+impl Engine {
+
+    pub fn apply(&mut self, command: Command) -> Applied {
+        match command {
+            Command::Limit(order) => Applied::Order(self.try_limit_order(order)),
+            Command::Market(order) => Applied::Order(self.try_market_order(order)),
+            Command::Cancel(id) => Applied::Cancel(self.try_cancel(id)),
+            Command::Replace { id, order } => Applied::Order(self.try_replace(id, order)),
+            Command::Amend { id, size } => Applied::Amend(self.try_amend(id, size)),
+            Command::Transition(phase) => Applied::Transition(self.transition(phase)),
+            Command::SetReferencePrice(price) => {
+                self.set_reference_price(price);
+                Applied::Configured
+            }
+            Command::SetPriceBands(bands) => {
+                self.set_price_bands(bands);
+                Applied::Configured
+            }
+        }
+    }
+}
@@ -3,12 +3,18 @@
 */
 
 use std::vec::Vec;
-use core::cmp::min;
-use crate::types::{Order, Price, OrderId, Execution, is_ask};
+use serde::{Serialize, Deserialize};
+use crate::types::{Order, Price, Size, Side, OrderId, Execution, is_ask};
+use crate::engine::matching::{MatchingPolicy, Fifo, Scratch};
+use crate::engine::auction::{Indicative, equilibrium};
+use crate::engine::session::{Phase, Reject, InvalidTransition};
+use crate::engine::bands::PriceBands;
+use crate::engine::snapshot::{Snapshot, SNAPSHOT_VERSION};
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct OrderIn {
-    order: Order,
-    id: OrderId,
+    pub order: Order,
+    pub id: OrderId,
 }
 
 pub struct Engine {
@@ -16,102 +22,160 @@ pub struct Engine {
     asks: Vec<OrderIn>,
     id: OrderId,
     pub execution_log: Vec<Execution>,
-    should_log: bool
+    should_log: bool,
+    policy: Box<dyn MatchingPolicy>,
+    // Price-time policies are matched directly, without the level scan or the policy call.
+    fifo: bool,
+    // Scratch space for level allocation, kept to avoid allocating per level.
+    fills: Vec<Size>,
+    scratch: Scratch,
+    phase: Phase,
+    // Auction tie-break and static band centre, updated to the clearing price of every uncross.
+    reference_price: Option<Price>,
+    // Dynamic band centre.
+    last_price: Option<Price>,
+    bands: PriceBands,
 }
 
 impl Engine {
 
     pub fn new() -> Engine {
-        Engine {
-            bids: Vec::<OrderIn>::new(),
-            asks: Vec::<OrderIn>::new(),
-            id: 1,
-            execution_log: Vec::new(),
-            should_log: false
-        }
+        Engine::with_policy(Box::new(Fifo), false)
     }
 
     pub fn new_debug() -> Engine {
+        Engine::with_policy(Box::new(Fifo), true)
+    }
+
+    pub fn with_policy(policy: Box<dyn MatchingPolicy>, debug: bool) -> Engine {
         Engine {
             bids: Vec::<OrderIn>::new(),
             asks: Vec::<OrderIn>::new(),
             id: 1,
             execution_log: Vec::new(),
-            should_log: true
+            should_log: debug,
+            fifo: policy.is_fifo(),
+            policy,
+            fills: Vec::new(),
+            scratch: Scratch::default(),
+            phase: Phase::Continuous,
+            reference_price: None,
+            last_price: None,
+            bands: PriceBands::new(),
+        }
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            next_id: self.id,
+            phase: self.phase,
+            reference_price: self.reference_price,
+            last_price: self.last_price,
+            bands: self.bands,
+            bids: self.bids.clone(),
+            asks: self.asks.clone(),
         }
     }
-    
+
+    // Continues from a snapshot, policy should be the one the snapshotted engine was using.
+    pub fn restore(snapshot: Snapshot, policy: Box<dyn MatchingPolicy>, debug: bool) -> Engine {
+        let mut engine = Engine::with_policy(policy, debug);
+        engine.id = snapshot.next_id;
+        engine.phase = snapshot.phase;
+        engine.reference_price = snapshot.reference_price;
+        engine.last_price = snapshot.last_price;
+        engine.bands = snapshot.bands;
+        engine.bids = snapshot.bids;
+        engine.asks = snapshot.asks;
+        engine
+    }
+
     // Helpers for cross
     fn hit_ask(bid: Price, ask: Price) -> bool {
-        return bid >= ask;
+        bid >= ask
     }
 
     fn hit_bid(ask: Price, bid: Price) -> bool {
-        return ask <= bid;
+        ask <= bid
     }
 
     // Helpers for queue
     fn priority_ask(ask_new: Price, ask_old: Price) -> bool {
-        return ask_new < ask_old;
+        ask_new < ask_old
     }
 
     fn priority_bid(bid_new: Price, bid_old: Price) -> bool {
-        return bid_new > bid_old;
+        bid_new > bid_old
     }
 
     // Original implementation used an undefined header function in the engine.h 
     // to be implemented should you want the backlog of orders to confirm the engine is valid.
     // The original implementation only implements this function when the engine is being tested - not scored.
     // Thus when the engine is being tested should_log is true, and when it is being scored should_log is false.
-    fn send_execution(order_1: &Order, order_2: &Order, log: &mut Vec<Execution>) {
+    fn send_execution(order_1: &Order, order_2: &Order, price: Price, quantity: Size, log: &mut Vec<Execution>) {
         let mut exec = order_1.clone();
-        exec.size = min(order_1.size, order_2.size);
+        exec.price = price;
+        exec.size = quantity;
 
         // Call callback now
         log.push(exec.clone());
 
         exec.trader = order_2.trader.clone();
-        exec.side = exec.side ^ 1;
+        exec.side ^= 1;
 
         // Callback for otherside of trade
         log.push(exec);
     }
 
-    fn trade(order: &mut Order, matched_order: &mut Order, log: &mut Vec<Execution>, should_log: bool) {
+    fn trade(order: &mut Order, matched_order: &mut Order, price: Price, quantity: Size, log: &mut Vec<Execution>, should_log: bool) {
         if should_log {
             // Send to execution report now.
-            Engine::send_execution(&order.clone(), &matched_order.clone(), log);
+            Engine::send_execution(order, matched_order, price, quantity, log);
         }
-        
 
-        // Completely fill matched
-        if order.size >= matched_order.size {
-            order.size -= matched_order.size;
-            // Removed via retain operation in cross
-            matched_order.size = 0;
-        }
-        // New order completely filled.
-        else {
-            matched_order.size -= order.size;
-            order.size = 0;
-        }
+        // Fully filled matched orders are removed via retain operation in cross
+        order.size -= quantity;
+        matched_order.size -= quantity;
     }
 
-    fn cross(&mut self, order: &mut Order) -> bool {
+    // Market orders ignore their own price and trade at the resting price.
+    // A level that would trade outside the dynamic band stops the sweep and halts the market.
+    fn cross(&mut self, order: &mut Order, market: bool) -> bool {
+        if self.fifo {
+            return self.cross_fifo(order, market);
+        }
+
         let isask = is_ask(order.side);
         let book = if isask { &mut self.bids } else { &mut self.asks };
         let cross_test = if isask { Engine::hit_bid } else { Engine::hit_ask };
         let log = &mut self.execution_log;
+        let anchor = self.last_price.or(self.reference_price);
 
-        for matched_order in book.iter_mut() {
-            if order.size == 0 {
+        let mut start = 0;
+        while order.size > 0 && start < book.len() && (market || cross_test(order.price, book[start].order.price)) {
+            // Orders at one price are contiguous and in time priority.
+            let level_price = book[start].order.price;
+            let price = if market { level_price } else { order.price };
+
+            if !PriceBands::within(self.bands.dynamic_band, anchor, price) {
+                self.phase = Phase::Halted;
                 break;
             }
-            if !cross_test(order.price, matched_order.order.price) {
-                break;
+            let end = start + book[start..].iter().take_while(|x| x.order.price == level_price).count();
+
+            self.fills.clear();
+            self.fills.resize(end - start, 0);
+            self.policy.allocate(order.size, &book[start..end], &mut self.fills, &mut self.scratch);
+
+            for (matched_order, &quantity) in book[start..end].iter_mut().zip(self.fills.iter()) {
+                if quantity > 0 {
+                    Engine::trade(order, &mut matched_order.order, price, quantity, log, self.should_log);
+                }
             }
+            self.last_price = Some(price);
 
-            Engine::trade(order, &mut matched_order.order, log, self.should_log);            
+            start = end;
         }
 
         book.retain(|x| x.order.size > 0);
@@ -119,7 +183,43 @@ impl Engine {
         order.size == 0
     }
 
-    fn queue(&mut self, order: Order) {
+    // cross for price-time priority, resting orders are taken one by one in book order.
+    fn cross_fifo(&mut self, order: &mut Order, market: bool) -> bool {
+        let isask = is_ask(order.side);
+        let book = if isask { &mut self.bids } else { &mut self.asks };
+        let cross_test = if isask { Engine::hit_bid } else { Engine::hit_ask };
+        let log = &mut self.execution_log;
+        let anchor = self.last_price.or(self.reference_price);
+
+        let mut idx = 0;
+        let mut level = None;
+        while order.size > 0 && idx < book.len() && (market || cross_test(order.price, book[idx].order.price)) {
+            let level_price = book[idx].order.price;
+            let price = if market { level_price } else { order.price };
+
+            // Checked once per level, as the general path does.
+            if level != Some(level_price) {
+                if !PriceBands::within(self.bands.dynamic_band, anchor, price) {
+                    self.phase = Phase::Halted;
+                    break;
+                }
+                level = Some(level_price);
+                self.last_price = Some(price);
+            }
+
+            let quantity = order.size.min(book[idx].order.size);
+            Engine::trade(order, &mut book[idx].order, price, quantity, log, self.should_log);
+            idx += 1;
+        }
+
+        // Only the last order traded with can be left partially filled.
+        let filled = book[..idx].iter().take_while(|x| x.order.size == 0).count();
+        book.drain(..filled);
+
+        order.size == 0
+    }
+
+    fn queue(&mut self, order: Order, id: OrderId) {
         let isask = is_ask(order.side);
         let book = if isask { &mut self.asks } else { &mut self.bids };
         let cross_test = if isask { Engine::priority_ask } else { Engine::priority_bid };
@@ -129,24 +229,183 @@ impl Engine {
             _ => book.len(),
         };
                             
-        let new_order = OrderIn { order: order, id: self.id };
+        let new_order = OrderIn { order, id };
         book.insert(insertion_index, new_order);
     }
 
-    pub fn limit_order(&mut self, mut order: Order) -> OrderId {
-        // Cross off as many shares as possible.
-        if !self.cross(&mut order) {
+    // Rejected orders return 0, which is never assigned. Use try_limit_order for the reason.
+    pub fn limit_order(&mut self, order: Order) -> OrderId {
+        self.try_limit_order(order).unwrap_or(0)
+    }
+
+    pub fn try_limit_order(&mut self, mut order: Order) -> Result<OrderId, Reject> {
+        if !self.phase.accepts_limit() {
+            return Err(Reject::Phase(self.phase));
+        }
+        if !PriceBands::within(self.bands.static_band, self.reference_price, order.price) {
+            return Err(Reject::OutsideBand);
+        }
+
+        // Cross off as many shares as possible, outside continuous trading orders are only collected.
+        // Whatever is left after a volatility halt rests on the book until the reopening auction.
+        if !self.phase.matches() || !self.cross(&mut order, false) {
             // Queue order if all shares not crossed off.
-            self.queue(order);
+            self.queue(order, self.id);
         }
         let return_id = self.id;
         self.id += 1;
-        return_id
+        Ok(return_id)
+    }
+
+    // Trades against the book at any price, whatever is not filled immediately is dropped.
+    // Rejected orders return 0, which is never assigned. Use try_market_order for the reason.
+    pub fn market_order(&mut self, order: Order) -> OrderId {
+        self.try_market_order(order).unwrap_or(0)
+    }
+
+    pub fn try_market_order(&mut self, mut order: Order) -> Result<OrderId, Reject> {
+        if !self.phase.accepts_market() {
+            return Err(Reject::Phase(self.phase));
+        }
+
+        self.cross(&mut order, true);
+
+        let return_id = self.id;
+        self.id += 1;
+        Ok(return_id)
     }
 
     pub fn cancel(&mut self, id: OrderId) {
+        let _ = self.try_cancel(id);
+    }
+
+    pub fn try_cancel(&mut self, id: OrderId) -> Result<(), Reject> {
+        if !self.phase.accepts_cancel() {
+            return Err(Reject::Phase(self.phase));
+        }
+
+        let before = self.asks.len() + self.bids.len();
         self.asks.retain(|x| x.id != id);
         self.bids.retain(|x| x.id != id);
+
+        if self.asks.len() + self.bids.len() == before {
+            return Err(Reject::UnknownOrder);
+        }
+        Ok(())
     }
 
-}
\ No newline at end of file
+    // Cancels order id and enters order in its place under a new id, losing time priority.
+    // The original stays on the book if the replacement is rejected.
+    pub fn try_replace(&mut self, id: OrderId, order: Order) -> Result<OrderId, Reject> {
+        if !self.phase.accepts_limit() {
+            return Err(Reject::Phase(self.phase));
+        }
+        if !PriceBands::within(self.bands.static_band, self.reference_price, order.price) {
+            return Err(Reject::OutsideBand);
+        }
+
+        self.try_cancel(id)?;
+        self.try_limit_order(order)
+    }
+
+    // Changes the size of a resting order, keeping its id. A smaller size keeps time priority,
+    // a larger one moves the order to the back of its price level and a size of 0 cancels it.
+    pub fn try_amend(&mut self, id: OrderId, size: Size) -> Result<(), Reject> {
+        if !self.phase.accepts_cancel() {
+            return Err(Reject::Phase(self.phase));
+        }
+        if size == 0 {
+            return self.try_cancel(id);
+        }
+
+        let (book, idx) = match self.bids.iter().position(|x| x.id == id) {
+            Some(idx) => (&mut self.bids, idx),
+            None => match self.asks.iter().position(|x| x.id == id) {
+                Some(idx) => (&mut self.asks, idx),
+                None => return Err(Reject::UnknownOrder),
+            },
+        };
+
+        if size <= book[idx].order.size {
+            book[idx].order.size = size;
+        } else {
+            let mut resting = book.remove(idx);
+            resting.order.size = size;
+            self.queue(resting.order, resting.id);
+        }
+        Ok(())
+    }
+
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    // Moves the session to another phase. Leaving an auction uncrosses the book, the result is returned.
+    pub fn transition(&mut self, to: Phase) -> Result<Option<Indicative>, InvalidTransition> {
+        if !self.phase.can_transition(to) {
+            return Err(InvalidTransition { from: self.phase, to });
+        }
+
+        let uncrossed = if self.phase == Phase::Auction { self.uncross() } else { None };
+        self.phase = to;
+        Ok(uncrossed)
+    }
+
+    pub fn set_reference_price(&mut self, price: Option<Price>) {
+        self.reference_price = price;
+    }
+
+    pub fn set_price_bands(&mut self, bands: PriceBands) {
+        self.bands = bands;
+    }
+
+    // What is left of a resting order, None once it has filled or been cancelled.
+    pub fn resting(&self, id: OrderId) -> Option<&Order> {
+        self.bids.iter().chain(self.asks.iter()).find(|x| x.id == id).map(|x| &x.order)
+    }
+
+    // One side of the book in priority order, asks for side 1.
+    pub fn book(&self, side: Side) -> &[OrderIn] {
+        if is_ask(side) { &self.asks } else { &self.bids }
+    }
+
+    // Price and volume the auction would uncross at if it ended now.
+    pub fn indicative(&self) -> Option<Indicative> {
+        equilibrium(&self.bids, &self.asks, self.reference_price)
+    }
+
+    // Executes every matched order at the single clearing price.
+    // Orders are filled in price-time priority regardless of the matching policy.
+    fn uncross(&mut self) -> Option<Indicative> {
+        let result = self.indicative()?;
+
+        let mut remaining = result.volume;
+        let (mut b, mut a) = (0, 0);
+        while remaining > 0 {
+            let quantity = remaining.min(self.bids[b].order.size).min(self.asks[a].order.size);
+            Engine::trade(&mut self.bids[b].order, &mut self.asks[a].order, result.price, quantity, &mut self.execution_log, self.should_log);
+            remaining -= quantity;
+
+            if self.bids[b].order.size == 0 {
+                b += 1;
+            }
+            if self.asks[a].order.size == 0 {
+                a += 1;
+            }
+        }
+
+        self.bids.retain(|x| x.order.size > 0);
+        self.asks.retain(|x| x.order.size > 0);
+
+        self.reference_price = Some(result.price);
+        self.last_price = Some(result.price);
+        Some(result)
+    }
+
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Engine::new()
+    }
+}
@@ -0,0 +1,231 @@
+/*
+    Allocation policies applied when an incoming order crosses a price level.
+*/
+
+use crate::types::Size;
+use crate::engine::engine::OrderIn;
+
+// Decides how an incoming quantity is shared among the resting orders of one price level.
+//
+// level holds the resting orders in time priority. fills has the same length and holds what each
+// resting order has already been allocated (zero when called by the engine), policies add to it.
+// An order may never be allocated more than its size, and the smallest of quantity and the level's
+// unallocated size must be handed out in total so the sweep never skips ahead of a better price.
+// scratch is working space owned by the engine, so levels can be allocated without allocating.
+pub trait MatchingPolicy {
+    fn allocate(&self, quantity: Size, level: &[OrderIn], fills: &mut [Size], scratch: &mut Scratch);
+
+    // Plain price-time priority, which the engine then matches order by order without calling allocate.
+    fn is_fifo(&self) -> bool {
+        false
+    }
+}
+
+// Buffers policies may use while allocating, cleared by whoever uses them.
+#[derive(Default)]
+pub struct Scratch {
+    pub capacities: Vec<Size>,
+    pub shares: Vec<Size>,
+    pub order: Vec<usize>,
+    pub zeroed: Vec<bool>,
+}
+
+fn capacity(resting: &OrderIn, fill: Size) -> Size {
+    resting.order.size - fill
+}
+
+// Price-time priority, the QuantCup behaviour and the engine default.
+pub struct Fifo;
+
+impl MatchingPolicy for Fifo {
+    fn allocate(&self, mut quantity: Size, level: &[OrderIn], fills: &mut [Size], _scratch: &mut Scratch) {
+        for (resting, fill) in level.iter().zip(fills.iter_mut()) {
+            if quantity == 0 {
+                break;
+            }
+            let extra = quantity.min(capacity(resting, *fill));
+            *fill += extra;
+            quantity -= extra;
+        }
+    }
+
+    fn is_fifo(&self) -> bool {
+        true
+    }
+}
+
+// Price-size-time priority, larger resting orders are filled first.
+pub struct PriceSizeTime;
+
+impl MatchingPolicy for PriceSizeTime {
+    fn allocate(&self, mut quantity: Size, level: &[OrderIn], fills: &mut [Size], scratch: &mut Scratch) {
+        let order = &mut scratch.order;
+        order.clear();
+        order.extend(0..level.len());
+        // Equal sizes keep time priority.
+        order.sort_unstable_by(|a, b| level[*b].order.size.cmp(&level[*a].order.size).then(a.cmp(b)));
+
+        for &idx in order.iter() {
+            if quantity == 0 {
+                break;
+            }
+            let extra = quantity.min(capacity(&level[idx], fills[idx]));
+            fills[idx] += extra;
+            quantity -= extra;
+        }
+    }
+}
+
+// How the quantity left over after rounding every share is handed out.
+pub enum Remainder {
+    // Resting orders in time priority.
+    Fifo,
+    // Largest resting order first, ties broken by time priority.
+    Largest,
+}
+
+pub enum Rounding {
+    Down,
+    Nearest,
+}
+
+// Quantity is split proportionally to the unallocated size of each resting order.
+pub struct ProRata {
+    // Shares smaller than this are zeroed and returned to the remainder. Those orders only receive
+    // remainder once every other order at the level is full.
+    pub min_allocation: Size,
+    pub rounding: Rounding,
+    pub remainder: Remainder,
+}
+
+impl ProRata {
+
+    pub fn new() -> ProRata {
+        ProRata {
+            min_allocation: 1,
+            rounding: Rounding::Down,
+            remainder: Remainder::Fifo,
+        }
+    }
+}
+
+impl Default for ProRata {
+    fn default() -> Self {
+        ProRata::new()
+    }
+}
+
+impl MatchingPolicy for ProRata {
+    fn allocate(&self, quantity: Size, level: &[OrderIn], fills: &mut [Size], scratch: &mut Scratch) {
+        let Scratch { capacities, shares, order, zeroed } = scratch;
+        capacities.clear();
+        capacities.extend(level.iter().zip(fills.iter()).map(|(x, f)| capacity(x, *f)));
+
+        let total: Size = capacities.iter().sum();
+        if quantity >= total {
+            for (fill, cap) in fills.iter_mut().zip(capacities.iter()) {
+                *fill += cap;
+            }
+            return;
+        }
+
+        shares.clear();
+        shares.extend(capacities.iter().map(|&cap| {
+            let exact = quantity as u128 * cap as u128;
+            let share = match self.rounding {
+                Rounding::Down => exact / total as u128,
+                Rounding::Nearest => (exact + total as u128 / 2) / total as u128,
+            };
+            share as Size
+        }));
+        zeroed.clear();
+        zeroed.extend(shares.iter().map(|&share| share < self.min_allocation));
+        for (share, _) in shares.iter_mut().zip(zeroed.iter()).filter(|x| *x.1) {
+            *share = 0;
+        }
+
+        // Rounding to nearest can hand out more than is available, take the excess back from the newest orders.
+        let mut allocated: Size = shares.iter().sum();
+        for share in shares.iter_mut().rev() {
+            if allocated <= quantity {
+                break;
+            }
+            let excess = (allocated - quantity).min(*share);
+            *share -= excess;
+            allocated -= excess;
+        }
+
+        order.clear();
+        order.extend(0..level.len());
+        if let Remainder::Largest = self.remainder {
+            // Equal sizes keep time priority.
+            order.sort_unstable_by(|a, b| capacities[*b].cmp(&capacities[*a]).then(a.cmp(b)));
+        }
+
+        // Zeroed orders come last, after every order that kept its share.
+        let mut remaining = quantity - allocated;
+        for &idx in order.iter().filter(|x| !zeroed[**x]).chain(order.iter().filter(|x| zeroed[**x])) {
+            if remaining == 0 {
+                break;
+            }
+            let extra = remaining.min(capacities[idx] - shares[idx]);
+            shares[idx] += extra;
+            remaining -= extra;
+        }
+
+        for (fill, share) in fills.iter_mut().zip(shares.iter()) {
+            *fill += share;
+        }
+    }
+}
+
+// The oldest order at the level is filled first, up to max_allocation, provided it is at least
+// min_size. Whatever is left is handed to the next policy.
+pub struct TopOrder {
+    pub min_size: Size,
+    pub max_allocation: Size,
+    pub then: Box<dyn MatchingPolicy>,
+}
+
+impl MatchingPolicy for TopOrder {
+    fn allocate(&self, mut quantity: Size, level: &[OrderIn], fills: &mut [Size], scratch: &mut Scratch) {
+        if let Some(top) = level.first() {
+            if top.order.size >= self.min_size {
+                let extra = quantity.min(self.max_allocation).min(capacity(top, fills[0]));
+                fills[0] += extra;
+                quantity -= extra;
+            }
+        }
+
+        self.then.allocate(quantity, level, fills, scratch);
+    }
+}
+
+// Lead market makers are guaranteed percent of the incoming quantity at a level, shared between
+// their orders in time priority. Whatever is left is handed to the next policy.
+pub struct Lmm {
+    pub traders: Vec<String>,
+    pub percent: u8,
+    pub then: Box<dyn MatchingPolicy>,
+}
+
+impl MatchingPolicy for Lmm {
+    fn allocate(&self, mut quantity: Size, level: &[OrderIn], fills: &mut [Size], scratch: &mut Scratch) {
+        let mut entitlement = (quantity as u128 * self.percent.min(100) as u128 / 100) as Size;
+
+        for (resting, fill) in level.iter().zip(fills.iter_mut()) {
+            if entitlement == 0 {
+                break;
+            }
+            if !self.traders.contains(&resting.order.trader) {
+                continue;
+            }
+            let extra = entitlement.min(capacity(resting, *fill));
+            *fill += extra;
+            entitlement -= extra;
+            quantity -= extra;
+        }
+
+        self.then.allocate(quantity, level, fills, scratch);
+    }
+}
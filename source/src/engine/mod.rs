@@ -1,2 +1,18 @@
 pub mod engine;
-mod test;
\ No newline at end of file
+pub mod matching;
+pub mod auction;
+pub mod session;
+pub mod bands;
+pub mod snapshot;
+pub mod command;
+mod test;
+
+// The engine as it was before any optimisation, kept as the reference for tests and benchmarks.
+#[allow(dead_code, clippy::all)]
+#[path = "../../../rust_engine_examples/baseline/baseline_engine.rs"]
+pub mod baseline;
+
+// The QuantCup 1 winner, benchmarked against the engine.
+#[allow(unused, clippy::all)]
+#[path = "../../../rust_engine_examples/winning/winning_engine.rs"]
+pub mod winning;
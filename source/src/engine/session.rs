@@ -0,0 +1,95 @@
+/*
+    Trading session phases and the commands each of them accepts.
+*/
+
+use std::fmt;
+use serde::{Serialize, Deserialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Phase {
+    // Limit orders and cancels are collected without matching.
+    PreOpen,
+    // As pre-open, with an indicative price. Leaving the auction uncrosses the book.
+    Auction,
+    // Normal matching, every command is accepted.
+    Continuous,
+    // Book frozen, only cancels are accepted. Reopened through an auction.
+    Halted,
+    // Nothing is accepted until the next pre-open.
+    Closed,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reject {
+    // The command is not accepted in this phase.
+    Phase(Phase),
+    // Cancel of an order that is not on the book.
+    UnknownOrder,
+    // Limit price outside the static price band.
+    OutsideBand,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidTransition {
+    pub from: Phase,
+    pub to: Phase,
+}
+
+impl Phase {
+
+    pub fn accepts_limit(self) -> bool {
+        matches!(self, Phase::PreOpen | Phase::Auction | Phase::Continuous)
+    }
+
+    pub fn accepts_market(self) -> bool {
+        self == Phase::Continuous
+    }
+
+    pub fn accepts_cancel(self) -> bool {
+        self != Phase::Closed
+    }
+
+    // Whether incoming orders are matched immediately.
+    pub fn matches(self) -> bool {
+        self == Phase::Continuous
+    }
+
+    pub fn can_transition(self, to: Phase) -> bool {
+        match self {
+            Phase::PreOpen => matches!(to, Phase::Auction | Phase::Closed),
+            Phase::Auction => matches!(to, Phase::Continuous | Phase::Closed),
+            Phase::Continuous => matches!(to, Phase::Auction | Phase::Halted | Phase::Closed),
+            Phase::Halted => matches!(to, Phase::Auction | Phase::Closed),
+            Phase::Closed => to == Phase::PreOpen,
+        }
+    }
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Phase::PreOpen => "pre-open",
+            Phase::Auction => "auction",
+            Phase::Continuous => "continuous",
+            Phase::Halted => "halted",
+            Phase::Closed => "closed",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for Reject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reject::Phase(phase) => write!(f, "not accepted while {}", phase),
+            Reject::UnknownOrder => write!(f, "unknown order"),
+            Reject::OutsideBand => write!(f, "price outside band"),
+        }
+    }
+}
+
+impl fmt::Display for InvalidTransition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cannot move from {} to {}", self.from, self.to)
+    }
+}
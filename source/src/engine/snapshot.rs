@@ -0,0 +1,90 @@
+/*
+    Versioned on-disk snapshot of the complete engine state.
+*/
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+use serde::{Serialize, Deserialize};
+use crate::types::{Price, OrderId};
+use crate::engine::engine::OrderIn;
+use crate::engine::session::Phase;
+use crate::engine::bands::PriceBands;
+
+// Bumped whenever the layout changes, older snapshots are refused rather than misread.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+// Everything needed to continue exactly where the engine stopped.
+// The matching policy is code rather than state and is supplied again on restore.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub next_id: OrderId,
+    pub phase: Phase,
+    pub reference_price: Option<Price>,
+    pub last_price: Option<Price>,
+    pub bands: PriceBands,
+    // Both sides in priority order.
+    pub bids: Vec<OrderIn>,
+    pub asks: Vec<OrderIn>,
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    Format(serde_json::Error),
+    Version(u32),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(e) => write!(f, "snapshot io error: {}", e),
+            SnapshotError::Format(e) => write!(f, "malformed snapshot: {}", e),
+            SnapshotError::Version(v) => write!(f, "snapshot version {} is not supported (expected {})", v, SNAPSHOT_VERSION),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<io::Error> for SnapshotError {
+    fn from(e: io::Error) -> Self {
+        SnapshotError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for SnapshotError {
+    fn from(e: serde_json::Error) -> Self {
+        SnapshotError::Format(e)
+    }
+}
+
+impl Snapshot {
+
+    // Written to a temporary file first and renamed so a crash never leaves a partial snapshot behind.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), SnapshotError> {
+        let path = path.as_ref();
+        let tmp = path.with_extension("tmp");
+
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+        drop(writer);
+
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Snapshot, SnapshotError> {
+        let reader = BufReader::new(File::open(path)?);
+        let snapshot: Snapshot = serde_json::from_reader(reader)?;
+
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::Version(snapshot.version));
+        }
+        Ok(snapshot)
+    }
+}
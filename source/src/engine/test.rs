@@ -1,9 +1,15 @@
 #[cfg(test)]
-
 mod engine_tests {
 
     use crate::types::{Order, OrderId, Execution};
     use crate::engine::engine::Engine;
+    use crate::engine::matching::{MatchingPolicy, Fifo, PriceSizeTime, ProRata, Remainder, Rounding, TopOrder, Lmm};
+    use crate::engine::auction::Indicative;
+    use crate::engine::session::{Phase, Reject, InvalidTransition};
+    use crate::engine::bands::PriceBands;
+    use crate::engine::snapshot::{Snapshot, SnapshotError};
+    use crate::engine::baseline;
+    use crate::feed::feed::get_raw_feed;
 
     struct TestState {
         order_id: OrderId,
@@ -19,6 +25,13 @@ mod engine_tests {
             }
         }
 
+        fn with_policy(policy: Box<dyn MatchingPolicy>) -> TestState {
+            TestState {
+                order_id: 0,
+                engine: Engine::with_policy(policy, true),
+            }
+        }
+
         fn feed_orders(&mut self, orders: Vec<Order>) {
             for order in orders {
                 let id = self.engine.limit_order(order);
@@ -40,6 +53,7 @@ mod engine_tests {
         }
 
         // Pre condition: expected_log.len() == self.engine.execution_log.len()
+        #[allow(clippy::ptr_arg, clippy::bool_assert_comparison)]
         fn verify_exec_log(&self, expected_log: &Vec<Order>) {
             let len = expected_log.len();
             let mut index = 0;
@@ -80,6 +94,14 @@ mod engine_tests {
         state.verify_exec_log(&execs);
 
     }
+
+    fn test_policy(policy: Box<dyn MatchingPolicy>, orders: Vec<Order>, execs: Vec<Execution>) {
+        let mut state = TestState::with_policy(policy);
+
+        state.feed_orders(orders);
+        state.verify_exec_count(execs.len());
+        state.verify_exec_log(&execs);
+    }
  
     #[test]
     fn test_ask() {
@@ -186,4 +208,403 @@ mod engine_tests {
 
         test_cancel(vec![ob101x100, ob101x25x.clone(), ob101x25x.clone(), ob101x50], vec![1, 4, 3], vec![oa101x50], vec![xb101x25x, xa101x25]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_pro_rata_split() {
+        let ob101x100a: Order = Order {symbol: String::from("JPM"), trader: String::from("A"), side: 0, price: 101, size: 100};
+        let ob101x300b: Order = Order {symbol: String::from("JPM"), trader: String::from("B"), side: 0, price: 101, size: 300};
+        let oa101x200: Order = Order {symbol: String::from("JPM"), trader: String::from("MAX"), side: 1, price: 101, size: 200};
+
+        let xa101x50: Execution = Execution {symbol: String::from("JPM"), trader: String::from("MAX"), side: 1, price: 101, size: 50};
+        let xb101x50a: Execution = Execution {symbol: String::from("JPM"), trader: String::from("A"), side: 0, price: 101, size: 50};
+        let xa101x150: Execution = Execution {symbol: String::from("JPM"), trader: String::from("MAX"), side: 1, price: 101, size: 150};
+        let xb101x150b: Execution = Execution {symbol: String::from("JPM"), trader: String::from("B"), side: 0, price: 101, size: 150};
+
+        test_policy(Box::new(ProRata::new()), vec![ob101x100a, ob101x300b, oa101x200], vec![xa101x50, xb101x50a, xa101x150, xb101x150b]);
+    }
+
+    #[test]
+    fn test_pro_rata_min_allocation_remainder() {
+        let ob101x10a: Order = Order {symbol: String::from("JPM"), trader: String::from("A"), side: 0, price: 101, size: 10};
+        let ob101x490b: Order = Order {symbol: String::from("JPM"), trader: String::from("B"), side: 0, price: 101, size: 490};
+        let ob101x500c: Order = Order {symbol: String::from("JPM"), trader: String::from("C"), side: 0, price: 101, size: 500};
+        let oa101x100: Order = Order {symbol: String::from("JPM"), trader: String::from("MAX"), side: 1, price: 101, size: 100};
+
+        let xa101x49: Execution = Execution {symbol: String::from("JPM"), trader: String::from("MAX"), side: 1, price: 101, size: 49};
+        let xb101x49b: Execution = Execution {symbol: String::from("JPM"), trader: String::from("B"), side: 0, price: 101, size: 49};
+        let xa101x50: Execution = Execution {symbol: String::from("JPM"), trader: String::from("MAX"), side: 1, price: 101, size: 50};
+        let xb101x50b: Execution = Execution {symbol: String::from("JPM"), trader: String::from("B"), side: 0, price: 101, size: 50};
+        let xb101x50c: Execution = Execution {symbol: String::from("JPM"), trader: String::from("C"), side: 0, price: 101, size: 50};
+        let xa101x51: Execution = Execution {symbol: String::from("JPM"), trader: String::from("MAX"), side: 1, price: 101, size: 51};
+        let xb101x51c: Execution = Execution {symbol: String::from("JPM"), trader: String::from("C"), side: 0, price: 101, size: 51};
+
+        // A's share of 1 is below the minimum and zeroed, the remainder skips A and goes to B by time priority.
+        let fifo = ProRata { min_allocation: 5, rounding: Rounding::Down, remainder: Remainder::Fifo };
+        test_policy(Box::new(fifo), vec![ob101x10a.clone(), ob101x490b.clone(), ob101x500c.clone(), oa101x100.clone()],
+            vec![xa101x50.clone(), xb101x50b, xa101x50, xb101x50c]);
+
+        // Remainder to the largest order instead.
+        let largest = ProRata { min_allocation: 5, rounding: Rounding::Down, remainder: Remainder::Largest };
+        test_policy(Box::new(largest), vec![ob101x10a, ob101x490b, ob101x500c, oa101x100], vec![xa101x49, xb101x49b, xa101x51, xb101x51c]);
+    }
+
+    #[test]
+    fn test_pro_rata_all_shares_zeroed() {
+        let ob101x10a: Order = Order {symbol: String::from("JPM"), trader: String::from("A"), side: 0, price: 101, size: 10};
+        let ob101x10b: Order = Order {symbol: String::from("JPM"), trader: String::from("B"), side: 0, price: 101, size: 10};
+        let oa101x2: Order = Order {symbol: String::from("JPM"), trader: String::from("MAX"), side: 1, price: 101, size: 2};
+
+        let xa101x2: Execution = Execution {symbol: String::from("JPM"), trader: String::from("MAX"), side: 1, price: 101, size: 2};
+        let xb101x2a: Execution = Execution {symbol: String::from("JPM"), trader: String::from("A"), side: 0, price: 101, size: 2};
+
+        // Every share is below the minimum, the level still trades and falls back to time priority.
+        let fifo = ProRata { min_allocation: 5, rounding: Rounding::Down, remainder: Remainder::Fifo };
+        test_policy(Box::new(fifo), vec![ob101x10a, ob101x10b, oa101x2], vec![xa101x2, xb101x2a]);
+    }
+
+    #[test]
+    fn test_pro_rata_rounding_nearest() {
+        let oa101x1a: Order = Order {symbol: String::from("JPM"), trader: String::from("A"), side: 1, price: 101, size: 1};
+        let oa101x1b: Order = Order {symbol: String::from("JPM"), trader: String::from("B"), side: 1, price: 101, size: 1};
+        let oa101x1c: Order = Order {symbol: String::from("JPM"), trader: String::from("C"), side: 1, price: 101, size: 1};
+        let ob101x2: Order = Order {symbol: String::from("JPM"), trader: String::from("MAX"), side: 0, price: 101, size: 2};
+
+        let xb101x1: Execution = Execution {symbol: String::from("JPM"), trader: String::from("MAX"), side: 0, price: 101, size: 1};
+        let xa101x1a: Execution = Execution {symbol: String::from("JPM"), trader: String::from("A"), side: 1, price: 101, size: 1};
+        let xa101x1b: Execution = Execution {symbol: String::from("JPM"), trader: String::from("B"), side: 1, price: 101, size: 1};
+
+        // Every share rounds up to 1, the newest order gives its share back.
+        let nearest = ProRata { min_allocation: 1, rounding: Rounding::Nearest, remainder: Remainder::Fifo };
+        test_policy(Box::new(nearest), vec![oa101x1a, oa101x1b, oa101x1c, ob101x2], vec![xb101x1.clone(), xa101x1a, xb101x1, xa101x1b]);
+    }
+
+    #[test]
+    fn test_pro_rata_sweep_levels() {
+        let oa100x50a: Order = Order {symbol: String::from("JPM"), trader: String::from("A"), side: 1, price: 100, size: 50};
+        let oa101x100b: Order = Order {symbol: String::from("JPM"), trader: String::from("B"), side: 1, price: 101, size: 100};
+        let oa101x100c: Order = Order {symbol: String::from("JPM"), trader: String::from("C"), side: 1, price: 101, size: 100};
+        let ob101x150: Order = Order {symbol: String::from("JPM"), trader: String::from("MAX"), side: 0, price: 101, size: 150};
+        let ob101x100: Order = Order {symbol: String::from("JPM"), trader: String::from("MAX"), side: 0, price: 101, size: 100};
+
+        let xb101x50: Execution = Execution {symbol: String::from("JPM"), trader: String::from("MAX"), side: 0, price: 101, size: 50};
+        let xa101x50a: Execution = Execution {symbol: String::from("JPM"), trader: String::from("A"), side: 1, price: 101, size: 50};
+        let xa101x50b: Execution = Execution {symbol: String::from("JPM"), trader: String::from("B"), side: 1, price: 101, size: 50};
+        let xa101x50c: Execution = Execution {symbol: String::from("JPM"), trader: String::from("C"), side: 1, price: 101, size: 50};
+
+        // The first level is taken whole, the rest is split evenly. The next bid takes what is left.
+        test_policy(Box::new(ProRata::new()), vec![oa100x50a, oa101x100b, oa101x100c, ob101x150, ob101x100],
+            vec![xb101x50.clone(), xa101x50a, xb101x50.clone(), xa101x50b.clone(), xb101x50.clone(), xa101x50c.clone(),
+                xb101x50.clone(), xa101x50b, xb101x50, xa101x50c]);
+    }
+
+    #[test]
+    fn test_price_size_time() {
+        let ob101x100a: Order = Order {symbol: String::from("JPM"), trader: String::from("A"), side: 0, price: 101, size: 100};
+        let ob101x300b: Order = Order {symbol: String::from("JPM"), trader: String::from("B"), side: 0, price: 101, size: 300};
+        let oa101x350: Order = Order {symbol: String::from("JPM"), trader: String::from("MAX"), side: 1, price: 101, size: 350};
+
+        let xa101x300: Execution = Execution {symbol: String::from("JPM"), trader: String::from("MAX"), side: 1, price: 101, size: 300};
+        let xb101x300b: Execution = Execution {symbol: String::from("JPM"), trader: String::from("B"), side: 0, price: 101, size: 300};
+        let xa101x50: Execution = Execution {symbol: String::from("JPM"), trader: String::from("MAX"), side: 1, price: 101, size: 50};
+        let xb101x50a: Execution = Execution {symbol: String::from("JPM"), trader: String::from("A"), side: 0, price: 101, size: 50};
+
+        test_policy(Box::new(PriceSizeTime), vec![ob101x100a, ob101x300b, oa101x350], vec![xa101x50, xb101x50a, xa101x300, xb101x300b]);
+    }
+
+    #[test]
+    fn test_top_order_then_pro_rata() {
+        let oa101x100a: Order = Order {symbol: String::from("JPM"), trader: String::from("A"), side: 1, price: 101, size: 100};
+        let oa101x100b: Order = Order {symbol: String::from("JPM"), trader: String::from("B"), side: 1, price: 101, size: 100};
+        let ob101x120: Order = Order {symbol: String::from("JPM"), trader: String::from("MAX"), side: 0, price: 101, size: 120};
+
+        let xb101x60: Execution = Execution {symbol: String::from("JPM"), trader: String::from("MAX"), side: 0, price: 101, size: 60};
+        let xa101x60a: Execution = Execution {symbol: String::from("JPM"), trader: String::from("A"), side: 1, price: 101, size: 60};
+        let xb101x60b: Execution = Execution {symbol: String::from("JPM"), trader: String::from("MAX"), side: 0, price: 101, size: 60};
+        let xa101x60b: Execution = Execution {symbol: String::from("JPM"), trader: String::from("B"), side: 1, price: 101, size: 60};
+
+        // A takes its 40 top order allocation, the remaining 80 is split 30/50 over A's 60 and B's 100.
+        let policy = TopOrder { min_size: 10, max_allocation: 40, then: Box::new(ProRata::new()) };
+        let xb101x70: Execution = Execution {symbol: String::from("JPM"), trader: String::from("MAX"), side: 0, price: 101, size: 70};
+        let xa101x70a: Execution = Execution {symbol: String::from("JPM"), trader: String::from("A"), side: 1, price: 101, size: 70};
+        let xb101x50: Execution = Execution {symbol: String::from("JPM"), trader: String::from("MAX"), side: 0, price: 101, size: 50};
+        let xa101x50b: Execution = Execution {symbol: String::from("JPM"), trader: String::from("B"), side: 1, price: 101, size: 50};
+        test_policy(Box::new(policy), vec![oa101x100a.clone(), oa101x100b.clone(), ob101x120.clone()], vec![xb101x70, xa101x70a, xb101x50, xa101x50b]);
+
+        // A top order below the minimum size gets no priority.
+        let policy = TopOrder { min_size: 1000, max_allocation: 40, then: Box::new(ProRata::new()) };
+        test_policy(Box::new(policy), vec![oa101x100a, oa101x100b, ob101x120], vec![xb101x60, xa101x60a, xb101x60b, xa101x60b]);
+    }
+
+    #[test]
+    fn test_lmm() {
+        let oa101x100a: Order = Order {symbol: String::from("JPM"), trader: String::from("A"), side: 1, price: 101, size: 100};
+        let oa101x100lmm: Order = Order {symbol: String::from("JPM"), trader: String::from("LMM"), side: 1, price: 101, size: 100};
+        let ob101x100: Order = Order {symbol: String::from("JPM"), trader: String::from("MAX"), side: 0, price: 101, size: 100};
+
+        let xb101x60: Execution = Execution {symbol: String::from("JPM"), trader: String::from("MAX"), side: 0, price: 101, size: 60};
+        let xa101x60a: Execution = Execution {symbol: String::from("JPM"), trader: String::from("A"), side: 1, price: 101, size: 60};
+        let xb101x40: Execution = Execution {symbol: String::from("JPM"), trader: String::from("MAX"), side: 0, price: 101, size: 40};
+        let xa101x40lmm: Execution = Execution {symbol: String::from("JPM"), trader: String::from("LMM"), side: 1, price: 101, size: 40};
+
+        // The market maker is guaranteed 40 despite arriving last, A fills the rest by time priority.
+        let policy = Lmm { traders: vec![String::from("LMM")], percent: 40, then: Box::new(Fifo) };
+        test_policy(Box::new(policy), vec![oa101x100a, oa101x100lmm, ob101x100], vec![xb101x60, xa101x60a, xb101x40, xa101x40lmm]);
+    }
+
+    #[test]
+    fn test_default_policy_matches_baseline() {
+        let flow = get_raw_feed().unwrap();
+        assert!(!flow.is_empty());
+
+        let mut engine = Engine::new_debug();
+        let mut fifo = Engine::with_policy(Box::new(Fifo), true);
+        let mut reference = baseline::Engine::new_debug();
+
+        for order in flow {
+            if order.price == 0 {
+                engine.cancel(order.size);
+                fifo.cancel(order.size);
+                reference.cancel(order.size);
+            } else {
+                let id = reference.limit_order(order.clone());
+                assert_eq!(engine.limit_order(order.clone()), id);
+                assert_eq!(fifo.limit_order(order), id);
+            }
+        }
+
+        assert!(!reference.execution_log.is_empty());
+        assert!(engine.execution_log == reference.execution_log);
+        assert!(fifo.execution_log == reference.execution_log);
+    }
+
+    fn order(trader: &str, side: u8, price: u16, size: u64) -> Order {
+        Order {symbol: String::from("JPM"), trader: String::from(trader), side, price, size}
+    }
+
+    #[test]
+    fn test_auction_uncross() {
+        let mut state = TestState::new();
+        state.engine.transition(Phase::Auction).unwrap();
+
+        state.feed_orders(vec![order("B1", 0, 102, 100), order("B2", 0, 101, 100), order("A1", 1, 100, 50), order("A2", 1, 101, 100)]);
+        state.verify_exec_count(0);
+
+        assert_eq!(state.engine.indicative(), Some(Indicative { price: 101, volume: 150, surplus: 50 }));
+        assert_eq!(state.engine.transition(Phase::Continuous), Ok(Some(Indicative { price: 101, volume: 150, surplus: 50 })));
+
+        // Everything trades at the clearing price in price-time priority, B2 keeps 50 on the book.
+        state.feed_orders(vec![order("A3", 1, 101, 50)]);
+        state.verify_exec_count(8);
+        state.verify_exec_log(&vec![order("B1", 0, 101, 50), order("A1", 1, 101, 50),
+            order("B1", 0, 101, 50), order("A2", 1, 101, 50),
+            order("B2", 0, 101, 50), order("A2", 1, 101, 50),
+            order("A3", 1, 101, 50), order("B2", 0, 101, 50)]);
+    }
+
+    #[test]
+    fn test_auction_no_cross() {
+        let mut state = TestState::new();
+        state.engine.transition(Phase::Auction).unwrap();
+
+        state.feed_orders(vec![order("B1", 0, 100, 100), order("A1", 1, 101, 100)]);
+
+        assert_eq!(state.engine.indicative(), None);
+        assert_eq!(state.engine.transition(Phase::Continuous), Ok(None));
+        state.verify_exec_count(0);
+    }
+
+    #[test]
+    fn test_auction_market_pressure() {
+        let mut state = TestState::new();
+        state.engine.transition(Phase::Auction).unwrap();
+        state.feed_orders(vec![order("B1", 0, 102, 100), order("A1", 1, 100, 60)]);
+        // Buyers are left over at every candidate price, so the highest wins.
+        assert_eq!(state.engine.indicative(), Some(Indicative { price: 102, volume: 60, surplus: 40 }));
+
+        let mut state = TestState::new();
+        state.engine.transition(Phase::Auction).unwrap();
+        state.feed_orders(vec![order("B1", 0, 102, 60), order("A1", 1, 100, 100)]);
+        assert_eq!(state.engine.indicative(), Some(Indicative { price: 100, volume: 60, surplus: -40 }));
+    }
+
+    #[test]
+    fn test_auction_reference_price() {
+        let mut state = TestState::new();
+        state.engine.transition(Phase::Auction).unwrap();
+        state.feed_orders(vec![order("B1", 0, 102, 100), order("A1", 1, 100, 100)]);
+
+        assert_eq!(state.engine.indicative(), Some(Indicative { price: 100, volume: 100, surplus: 0 }));
+
+        state.engine.set_reference_price(Some(105));
+        assert_eq!(state.engine.indicative(), Some(Indicative { price: 102, volume: 100, surplus: 0 }));
+    }
+
+    #[test]
+    fn test_session_day() {
+        let mut state = TestState::new();
+        state.engine.transition(Phase::Halted).unwrap();
+        state.engine.transition(Phase::Closed).unwrap();
+        assert_eq!(state.engine.transition(Phase::Continuous), Err(InvalidTransition { from: Phase::Closed, to: Phase::Continuous }));
+        assert_eq!(state.engine.try_limit_order(order("B1", 0, 101, 100)), Err(Reject::Phase(Phase::Closed)));
+        assert_eq!(state.engine.try_cancel(1), Err(Reject::Phase(Phase::Closed)));
+
+        // Pre-open collects orders and cancels without matching, market orders are refused.
+        state.engine.transition(Phase::PreOpen).unwrap();
+        state.feed_orders(vec![order("B1", 0, 101, 100), order("A1", 1, 100, 40), order("A2", 1, 101, 100)]);
+        assert_eq!(state.engine.try_market_order(order("B2", 0, 0, 10)), Err(Reject::Phase(Phase::PreOpen)));
+        state.engine.cancel(3);
+        state.verify_exec_count(0);
+
+        assert_eq!(state.engine.transition(Phase::Continuous), Err(InvalidTransition { from: Phase::PreOpen, to: Phase::Continuous }));
+        state.engine.transition(Phase::Auction).unwrap();
+        assert_eq!(state.engine.transition(Phase::Continuous), Ok(Some(Indicative { price: 101, volume: 40, surplus: 60 })));
+        state.verify_exec_count(2);
+
+        // A halt freezes the book but still allows cancels, trading resumes through an auction.
+        state.engine.transition(Phase::Halted).unwrap();
+        assert_eq!(state.engine.try_limit_order(order("A3", 1, 101, 60)), Err(Reject::Phase(Phase::Halted)));
+        assert_eq!(state.engine.try_market_order(order("A3", 1, 0, 60)), Err(Reject::Phase(Phase::Halted)));
+        assert_eq!(state.engine.try_cancel(1), Ok(()));
+        assert_eq!(state.engine.try_cancel(1), Err(Reject::UnknownOrder));
+        state.engine.transition(Phase::Auction).unwrap();
+        assert_eq!(state.engine.transition(Phase::Continuous), Ok(None));
+        assert_eq!(state.engine.phase(), Phase::Continuous);
+    }
+
+    #[test]
+    fn test_market_order() {
+        let mut state = TestState::new();
+        state.feed_orders(vec![order("A1", 1, 101, 50), order("A2", 1, 103, 50)]);
+
+        // Sweeps both levels at the resting prices, the unfilled 20 is dropped.
+        assert_eq!(state.engine.market_order(order("B1", 0, 0, 120)), 3);
+        state.order_id += 1;
+        state.verify_exec_count(4);
+        state.verify_exec_log(&vec![order("B1", 0, 101, 50), order("A1", 1, 101, 50), order("B1", 0, 103, 50), order("A2", 1, 103, 50)]);
+
+        state.feed_orders(vec![order("A3", 1, 104, 10)]);
+        state.verify_exec_count(4);
+    }
+
+    #[test]
+    fn test_static_band() {
+        let mut state = TestState::new();
+        state.engine.set_price_bands(PriceBands { static_band: Some(5), dynamic_band: None });
+
+        // No reference price yet, nothing to measure against.
+        state.feed_orders(vec![order("B1", 0, 50, 100)]);
+
+        state.engine.set_reference_price(Some(100));
+        assert_eq!(state.engine.try_limit_order(order("B2", 0, 94, 100)), Err(Reject::OutsideBand));
+        assert_eq!(state.engine.try_limit_order(order("A1", 1, 106, 100)), Err(Reject::OutsideBand));
+        state.feed_orders(vec![order("B2", 0, 95, 100), order("A1", 1, 105, 100)]);
+        state.verify_exec_count(0);
+    }
+
+    #[test]
+    fn test_dynamic_band_halt() {
+        let mut state = TestState::new();
+        state.engine.set_price_bands(PriceBands { static_band: None, dynamic_band: Some(2) });
+        state.engine.set_reference_price(Some(100));
+
+        state.feed_orders(vec![order("A1", 1, 101, 50), order("A2", 1, 102, 50), order("A3", 1, 110, 50)]);
+
+        // Limit orders trade at their own price, so a fat finger bid at 200 is outside the band
+        // even though the asks it crosses are not.
+        state.feed_orders(vec![order("B1", 0, 200, 150)]);
+        assert_eq!(state.engine.phase(), Phase::Halted);
+        state.verify_exec_count(0);
+
+        // It rests on the crossed book and is resolved by the reopening auction.
+        state.engine.transition(Phase::Auction).unwrap();
+        assert_eq!(state.engine.transition(Phase::Continuous), Ok(Some(Indicative { price: 110, volume: 150, surplus: 0 })));
+        state.verify_exec_count(6);
+    }
+
+    #[test]
+    fn test_dynamic_band_limit_within() {
+        let mut state = TestState::new();
+        state.engine.set_price_bands(PriceBands { static_band: None, dynamic_band: Some(2) });
+        state.engine.set_reference_price(Some(100));
+
+        state.feed_orders(vec![order("A1", 1, 101, 50), order("A2", 1, 110, 50)]);
+
+        // Trades at 102, inside the band, and stops at the 110 level because it does not cross it.
+        state.feed_orders(vec![order("B1", 0, 102, 100)]);
+        assert_eq!(state.engine.phase(), Phase::Continuous);
+        state.verify_exec_log(&vec![order("B1", 0, 102, 50), order("A1", 1, 102, 50)]);
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        let flow = get_raw_feed().unwrap();
+        let (first, second) = flow.split_at(flow.len() / 2);
+        let path = std::env::temp_dir().join(format!("orderbook_snapshot_{}.json", std::process::id()));
+
+        let mut engine = Engine::new_debug();
+        engine.set_price_bands(PriceBands { static_band: Some(1000), dynamic_band: None });
+        engine.set_reference_price(Some(5000));
+        for order in first {
+            if order.price == 0 { engine.cancel(order.size); } else { engine.limit_order(order.clone()); }
+        }
+
+        engine.snapshot().save(&path).unwrap();
+        let mut restored = Engine::restore(Snapshot::load(&path).unwrap(), Box::new(Fifo), true);
+        std::fs::remove_file(&path).unwrap();
+
+        let logged = engine.execution_log.len();
+        for order in second {
+            if order.price == 0 {
+                assert_eq!(restored.try_cancel(order.size), engine.try_cancel(order.size));
+            } else {
+                assert_eq!(restored.try_limit_order(order.clone()), engine.try_limit_order(order.clone()));
+            }
+        }
+
+        assert!(engine.execution_log.len() > logged);
+        assert!(restored.execution_log[..] == engine.execution_log[logged..]);
+    }
+
+    #[test]
+    fn test_snapshot_version() {
+        let path = std::env::temp_dir().join(format!("orderbook_snapshot_version_{}.json", std::process::id()));
+
+        let mut snapshot = Engine::new().snapshot();
+        snapshot.version += 1;
+        snapshot.save(&path).unwrap();
+
+        let loaded = Snapshot::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(loaded, Err(SnapshotError::Version(_))));
+    }
+
+    #[test]
+    fn test_replace() {
+        let mut state = TestState::new();
+        state.feed_orders(vec![order("B1", 0, 100, 50), order("B2", 0, 100, 50)]);
+
+        // Replacing B1 gives it a new id behind B2.
+        assert_eq!(state.engine.try_replace(1, order("B1", 0, 100, 60)), Ok(3));
+        assert_eq!(state.engine.try_replace(1, order("B1", 0, 100, 60)), Err(Reject::UnknownOrder));
+        state.order_id = 3;
+
+        state.feed_orders(vec![order("A1", 1, 100, 50)]);
+        state.verify_exec_log(&vec![order("A1", 1, 100, 50), order("B2", 0, 100, 50)]);
+    }
+
+    #[test]
+    fn test_amend() {
+        let mut state = TestState::new();
+        state.feed_orders(vec![order("B1", 0, 100, 50), order("B2", 0, 100, 50), order("B3", 0, 100, 50)]);
+
+        // Down keeps priority, up loses it, zero cancels.
+        assert_eq!(state.engine.try_amend(1, 20), Ok(()));
+        assert_eq!(state.engine.try_amend(2, 80), Ok(()));
+        assert_eq!(state.engine.try_amend(3, 0), Ok(()));
+        assert_eq!(state.engine.try_amend(3, 10), Err(Reject::UnknownOrder));
+
+        state.feed_orders(vec![order("A1", 1, 100, 100)]);
+        state.verify_exec_count(4);
+        state.verify_exec_log(&vec![order("A1", 1, 100, 20), order("B1", 0, 100, 20), order("A1", 1, 100, 80), order("B2", 0, 100, 80)]);
+    }
+}
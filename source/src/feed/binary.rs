@@ -0,0 +1,211 @@
+/*
+    Fixed-width binary feed, memory-mapped so captured feeds replay without parsing.
+
+    Layout (little endian)
+        header, 32 bytes:   magic "QCFEED\0\0" | version: u32 | record size: u32 | records: u64 | reserved: 8
+        record, 32 bytes:   symbol: [u8; 8] | trader: [u8; 8] | size: u64 | price: u16 | side: u8 | reserved: 5
+
+    Symbols and traders are zero padded. As in the csv, a record with price 0 is a cancel of order size.
+*/
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::str;
+use memmap2::Mmap;
+use crate::types::{Order, Price, Size, Side};
+use crate::feed::feed::{load_feed, Mode, FeedError};
+
+pub const MAGIC: [u8; 8] = *b"QCFEED\0\0";
+pub const VERSION: u32 = 1;
+pub const HEADER_SIZE: usize = 32;
+pub const RECORD_SIZE: usize = 32;
+pub const NAME_SIZE: usize = 8;
+
+#[derive(Debug)]
+pub enum BinaryFeedError {
+    Io(io::Error),
+    Feed(FeedError),
+    // Not a feed this version can read.
+    Header(String),
+    // Symbol or trader longer than NAME_SIZE bytes.
+    NameTooLong { record: usize, name: String },
+}
+
+impl fmt::Display for BinaryFeedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BinaryFeedError::Io(e) => write!(f, "binary feed io error: {}", e),
+            BinaryFeedError::Feed(e) => write!(f, "{}", e),
+            BinaryFeedError::Header(reason) => write!(f, "bad binary feed header: {}", reason),
+            BinaryFeedError::NameTooLong { record, name } =>
+                write!(f, "record {}: '{}' does not fit in {} bytes", record, name, NAME_SIZE),
+        }
+    }
+}
+
+impl std::error::Error for BinaryFeedError {}
+
+impl From<io::Error> for BinaryFeedError {
+    fn from(e: io::Error) -> Self {
+        BinaryFeedError::Io(e)
+    }
+}
+
+// One record as it sits in the file. Every field is a byte array so records can be viewed in
+// place whatever the alignment of the mapping.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct BinaryOrder {
+    symbol: [u8; NAME_SIZE],
+    trader: [u8; NAME_SIZE],
+    size: [u8; 8],
+    price: [u8; 2],
+    side: u8,
+    reserved: [u8; 5],
+}
+
+fn pack_name(record: usize, name: &str) -> Result<[u8; NAME_SIZE], BinaryFeedError> {
+    if name.len() > NAME_SIZE {
+        return Err(BinaryFeedError::NameTooLong { record, name: name.to_string() });
+    }
+    let mut packed = [0u8; NAME_SIZE];
+    packed[..name.len()].copy_from_slice(name.as_bytes());
+    Ok(packed)
+}
+
+fn unpack_name(name: &[u8; NAME_SIZE]) -> &str {
+    let len = name.iter().position(|x| *x == 0).unwrap_or(NAME_SIZE);
+    str::from_utf8(&name[..len]).unwrap_or("")
+}
+
+impl BinaryOrder {
+
+    pub fn pack(record: usize, order: &Order) -> Result<BinaryOrder, BinaryFeedError> {
+        Ok(BinaryOrder {
+            symbol: pack_name(record, &order.symbol)?,
+            trader: pack_name(record, &order.trader)?,
+            size: order.size.to_le_bytes(),
+            price: order.price.to_le_bytes(),
+            side: order.side,
+            reserved: [0; 5],
+        })
+    }
+
+    pub fn symbol(&self) -> &str {
+        unpack_name(&self.symbol)
+    }
+
+    pub fn trader(&self) -> &str {
+        unpack_name(&self.trader)
+    }
+
+    pub fn size(&self) -> Size {
+        Size::from_le_bytes(self.size)
+    }
+
+    pub fn price(&self) -> Price {
+        Price::from_le_bytes(self.price)
+    }
+
+    pub fn side(&self) -> Side {
+        self.side
+    }
+
+    pub fn to_order(&self) -> Order {
+        Order {
+            symbol: self.symbol().to_string(),
+            trader: self.trader().to_string(),
+            side: self.side(),
+            price: self.price(),
+            size: self.size(),
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        // BinaryOrder is repr(C), made only of u8 arrays, so it has no padding.
+        unsafe { std::slice::from_raw_parts(self as *const BinaryOrder as *const u8, RECORD_SIZE) }
+    }
+}
+
+pub fn write_binary_feed<P: AsRef<Path>>(path: P, flow: &[Order]) -> Result<(), BinaryFeedError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    writer.write_all(&MAGIC)?;
+    writer.write_all(&VERSION.to_le_bytes())?;
+    writer.write_all(&(RECORD_SIZE as u32).to_le_bytes())?;
+    writer.write_all(&(flow.len() as u64).to_le_bytes())?;
+    writer.write_all(&[0u8; 8])?;
+
+    for (idx, order) in flow.iter().enumerate() {
+        writer.write_all(BinaryOrder::pack(idx, order)?.as_bytes())?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+// Converts a csv feed, returns the number of records written.
+pub fn convert_csv<P: AsRef<Path>, Q: AsRef<Path>>(csv_path: P, binary_path: Q) -> Result<usize, BinaryFeedError> {
+    let flow = load_feed(csv_path, Mode::Strict).map_err(BinaryFeedError::Feed)?.flow;
+
+    write_binary_feed(binary_path, &flow)?;
+    Ok(flow.len())
+}
+
+pub struct BinaryFeed {
+    map: Mmap,
+    records: usize,
+}
+
+impl BinaryFeed {
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<BinaryFeed, BinaryFeedError> {
+        let file = File::open(path)?;
+        // The feed must not be modified while it is mapped, as for any memory-mapped file.
+        let map = unsafe { Mmap::map(&file)? };
+
+        if map.len() < HEADER_SIZE || map[0..8] != MAGIC {
+            return Err(BinaryFeedError::Header(String::from("not a binary feed")));
+        }
+
+        let field = |at: usize| u32::from_le_bytes([map[at], map[at + 1], map[at + 2], map[at + 3]]);
+        let version = field(8);
+        if version != VERSION {
+            return Err(BinaryFeedError::Header(format!("version {} is not supported (expected {})", version, VERSION)));
+        }
+        if field(12) as usize != RECORD_SIZE {
+            return Err(BinaryFeedError::Header(format!("record size {} (expected {})", field(12), RECORD_SIZE)));
+        }
+
+        let mut count = [0u8; 8];
+        count.copy_from_slice(&map[16..24]);
+        let declared = u64::from_le_bytes(count);
+        // Checked, a crafted count must not wrap around to the file's length.
+        let expected = usize::try_from(declared).ok()
+            .and_then(|x| x.checked_mul(RECORD_SIZE))
+            .and_then(|x| x.checked_add(HEADER_SIZE));
+        if expected != Some(map.len()) {
+            return Err(BinaryFeedError::Header(format!("{} records declared but file is {} bytes", declared, map.len())));
+        }
+        let records = declared as usize;
+
+        Ok(BinaryFeed { map, records })
+    }
+
+    pub fn len(&self) -> usize {
+        self.records
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records == 0
+    }
+
+    pub fn records(&self) -> &[BinaryOrder] {
+        let body = &self.map[HEADER_SIZE..];
+        // Length checked in open, BinaryOrder has alignment 1 and every bit pattern is valid.
+        unsafe { std::slice::from_raw_parts(body.as_ptr() as *const BinaryOrder, self.records) }
+    }
+}
@@ -0,0 +1,184 @@
+/*
+    Feed schema with an explicit action per row, so a feed can drive the whole order API.
+
+    The version of a csv feed is identified by its header
+        v1 (legacy)     symbol,trader,side,price,size
+        v2              action,id,symbol,trader,side,price,size
+
+    v2 actions and the columns they use (others are left empty)
+        new         symbol, trader, side, price, size       limit order
+        market      symbol, trader, side, size              market order
+        cancel      id
+        replace     id, symbol, trader, side, price, size   cancel id and enter a new limit order
+        amend       id, size                                change the size of order id
+
+    v1 rows are limit orders, except price 0 which cancels order id = size.
+*/
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use serde::{Serialize, Deserialize};
+use crate::types::{Order, OrderId, Price, Size, Side};
+use crate::engine::command::Command;
+use crate::feed::feed::{read_feed, row_error, to_command, Mode, FeedError};
+
+pub const LEGACY_HEADER: [&str; 5] = ["symbol", "trader", "side", "price", "size"];
+pub const V2_HEADER: [&str; 7] = ["action", "id", "symbol", "trader", "side", "price", "size"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Version {
+    Legacy,
+    V2,
+}
+
+pub struct CommandFeed {
+    pub version: Version,
+    pub commands: Vec<Command>,
+    // Rows dropped in lenient mode, always empty in strict mode.
+    pub skipped: Vec<FeedError>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct Row {
+    action: String,
+    id: Option<OrderId>,
+    symbol: Option<String>,
+    trader: Option<String>,
+    side: Option<Side>,
+    price: Option<Price>,
+    size: Option<Size>,
+}
+
+fn required<T: Clone>(value: &Option<T>, field: &'static str) -> Result<T, (Option<&'static str>, String)> {
+    value.clone().ok_or((Some(field), String::from("missing")))
+}
+
+fn side(row: &Row) -> Result<Side, (Option<&'static str>, String)> {
+    match required(&row.side, "side")? {
+        side @ 0..=1 => Ok(side),
+        side => Err((Some("side"), format!("{} is neither 0 (bid) nor 1 (ask)", side))),
+    }
+}
+
+fn order(row: &Row, price: Price) -> Result<Order, (Option<&'static str>, String)> {
+    Ok(Order {
+        symbol: required(&row.symbol, "symbol")?,
+        trader: required(&row.trader, "trader")?,
+        side: side(row)?,
+        price,
+        size: required(&row.size, "size")?,
+    })
+}
+
+fn to_v2_command(row: &Row) -> Result<Command, (Option<&'static str>, String)> {
+    match row.action.as_str() {
+        "new" => Ok(Command::Limit(order(row, required(&row.price, "price")?)?)),
+        "market" => Ok(Command::Market(order(row, row.price.unwrap_or(0))?)),
+        "cancel" => Ok(Command::Cancel(required(&row.id, "id")?)),
+        "replace" => Ok(Command::Replace { id: required(&row.id, "id")?, order: order(row, required(&row.price, "price")?)? }),
+        "amend" => Ok(Command::Amend { id: required(&row.id, "id")?, size: required(&row.size, "size")? }),
+        other => Err((Some("action"), format!("unknown action '{}'", other))),
+    }
+}
+
+fn from_command(command: &Command) -> Option<Row> {
+    let with_order = |action: &str, id: Option<OrderId>, order: &Order| Row {
+        action: action.to_string(),
+        id,
+        symbol: Some(order.symbol.clone()),
+        trader: Some(order.trader.clone()),
+        side: Some(order.side),
+        price: Some(order.price),
+        size: Some(order.size),
+    };
+
+    match command {
+        Command::Limit(order) => Some(with_order("new", None, order)),
+        Command::Market(order) => Some(Row { price: None, ..with_order("market", None, order) }),
+        Command::Cancel(id) => Some(Row { action: String::from("cancel"), id: Some(*id), ..Row::default() }),
+        Command::Replace { id, order } => Some(with_order("replace", Some(*id), order)),
+        Command::Amend { id, size } => Some(Row { action: String::from("amend"), id: Some(*id), size: Some(*size), ..Row::default() }),
+        _ => None,
+    }
+}
+
+fn read_v2<R: io::Read>(reader: R, file: &str, mode: Mode) -> Result<CommandFeed, FeedError> {
+    let mut reader = csv::Reader::from_reader(reader);
+    let headers = match reader.headers() {
+        Ok(headers) => headers.clone(),
+        Err(e) => return Err(row_error(file, e, &csv::StringRecord::new())),
+    };
+    let mut feed = CommandFeed { version: Version::V2, commands: Vec::new(), skipped: Vec::new() };
+
+    let mut rows = reader.deserialize::<Row>();
+    loop {
+        let line = rows.reader().position().line();
+        let error = match rows.next() {
+            None => break,
+            Some(Ok(row)) => match to_v2_command(&row) {
+                Ok(command) => {
+                    feed.commands.push(command);
+                    continue;
+                }
+                Err((field, message)) => FeedError::Row { file: file.to_string(), line, field: field.map(String::from), message },
+            },
+            Some(Err(e)) => row_error(file, e, &headers),
+        };
+
+        match (mode, error) {
+            (_, error @ FeedError::Io { .. }) | (Mode::Strict, error) => return Err(error),
+            (Mode::Lenient, error) => feed.skipped.push(error),
+        }
+    }
+
+    Ok(feed)
+}
+
+// Reads a v1 or v2 csv feed, telling them apart by the header.
+pub fn read_commands<R: io::BufRead>(mut reader: R, file: &str, mode: Mode) -> Result<CommandFeed, FeedError> {
+    let io_error = |error| FeedError::Io { file: file.to_string(), error };
+
+    let mut header = String::new();
+    reader.read_line(&mut header).map_err(io_error)?;
+    let columns: Vec<&str> = header.trim_end().split(',').map(|x| x.trim()).collect();
+
+    let rest = io::Cursor::new(header.clone()).chain(reader);
+    if columns == V2_HEADER {
+        read_v2(rest, file, mode)
+    } else if columns == LEGACY_HEADER {
+        let legacy = read_feed(rest, file, mode)?;
+        Ok(CommandFeed {
+            version: Version::Legacy,
+            commands: legacy.flow.iter().map(to_command).collect(),
+            skipped: legacy.skipped,
+        })
+    } else {
+        Err(FeedError::Row { file: file.to_string(), line: 1, field: None, message: format!("unrecognised header '{}'", header.trim_end()) })
+    }
+}
+
+pub fn load_commands<P: AsRef<Path>>(path: P, mode: Mode) -> Result<CommandFeed, FeedError> {
+    let file = path.as_ref().display().to_string();
+    let reader = File::open(&path).map_err(|error| FeedError::Io { file: file.clone(), error })?;
+    read_commands(io::BufReader::new(reader), &file, mode)
+}
+
+// Writes commands as a v2 feed. Session and configuration commands have no row and are refused.
+pub fn write_commands<W: io::Write>(writer: W, commands: &[Command]) -> Result<(), csv::Error> {
+    let mut writer = csv::Writer::from_writer(writer);
+
+    for command in commands {
+        let row = from_command(command).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("{:?} cannot be written to a feed", command))
+        })?;
+        writer.serialize(row)?;
+    }
+
+    // An empty feed still needs its header to be recognised.
+    if commands.is_empty() {
+        writer.write_record(V2_HEADER)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
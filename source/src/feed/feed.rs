@@ -1,27 +1,202 @@
 use csv;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::path::Path;
 
-use crate::types::{Order};
+use crate::types::Order;
+use crate::engine::command::Command;
+use crate::feed::binary::BinaryFeed;
 
-pub fn get_raw_feed() -> Vec<Order> {
-    let mut flow = Vec::new();
+// The QuantCup flow, found whatever directory the binary runs from.
+pub const SCORE_FEED: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/data/score_feed.csv");
 
-    let path = "./data/score_feed.csv";
-    let mut reader = match csv::Reader::from_path(path) {
-        Err(e) => {
-            println!("{}", e);
-            return vec![];
-        },
-        Ok(f) => f,
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    // The first bad row fails the load.
+    Strict,
+    // Bad rows are skipped and reported in Feed::skipped.
+    Lenient,
+}
+
+#[derive(Debug)]
+pub enum FeedError {
+    // The feed could not be opened or read.
+    Io { file: String, error: io::Error },
+    // A row that does not parse. field is None when the row as a whole is malformed.
+    Row { file: String, line: u64, field: Option<String>, message: String },
+}
+
+impl fmt::Display for FeedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FeedError::Io { file, error } => write!(f, "{}: {}", file, error),
+            FeedError::Row { file, line, field: Some(field), message } => write!(f, "{}:{}: field '{}': {}", file, line, field, message),
+            FeedError::Row { file, line, field: None, message } => write!(f, "{}:{}: {}", file, line, message),
+        }
+    }
+}
+
+impl std::error::Error for FeedError {}
+
+pub struct Feed {
+    pub flow: Vec<Order>,
+    // Rows dropped in lenient mode, always empty in strict mode.
+    pub skipped: Vec<FeedError>,
+}
+
+pub(crate) fn row_error(file: &str, error: csv::Error, headers: &csv::StringRecord) -> FeedError {
+    let line = error.position().map(|x| x.line()).unwrap_or(0);
+    let row = |field: Option<String>, message: String| FeedError::Row { file: file.to_string(), line, field, message };
+
+    match error.into_kind() {
+        csv::ErrorKind::Io(error) => FeedError::Io { file: file.to_string(), error },
+        csv::ErrorKind::Deserialize { err, .. } => {
+            let field = err.field().and_then(|idx| headers.get(idx as usize)).map(String::from);
+            row(field, err.kind().to_string())
+        }
+        csv::ErrorKind::UnequalLengths { expected_len, len, .. } =>
+            row(None, format!("expected {} fields, found {}", expected_len, len)),
+        kind => row(None, format!("{:?}", kind)),
+    }
+}
+
+// Checks what the csv types cannot express.
+fn validate(order: &Order) -> Result<(), (&'static str, String)> {
+    if order.side > 1 {
+        return Err(("side", format!("{} is neither 0 (bid) nor 1 (ask)", order.side)));
+    }
+    Ok(())
+}
+
+// A trailing comma, as on the last row of the QuantCup feed, is an empty extra field and is dropped.
+fn trim_trailing_comma(record: &mut csv::StringRecord, fields: usize) {
+    if record.len() == fields + 1 && record.get(fields) == Some("") {
+        record.truncate(fields);
+    }
+}
+
+// file names the source in errors.
+pub fn read_feed<R: io::Read>(reader: R, file: &str, mode: Mode) -> Result<Feed, FeedError> {
+    // Flexible so a trailing comma reaches trim_trailing_comma, row lengths are checked below.
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(reader);
+    let headers = match reader.headers() {
+        Ok(headers) => headers.clone(),
+        Err(e) => return Err(row_error(file, e, &csv::StringRecord::new())),
     };
 
-    for result in reader.deserialize() {
-        let record: Order = match result {
-            Err(_e) => continue,
-            Ok(f) => f,
+    let mut feed = Feed { flow: Vec::new(), skipped: Vec::new() };
+    let mut record = csv::StringRecord::new();
+    loop {
+        let line = reader.position().line();
+        let error = match reader.read_record(&mut record) {
+            Ok(false) => break,
+            Ok(true) => {
+                trim_trailing_comma(&mut record, headers.len());
+                if record.len() == headers.len() {
+                    match record.deserialize::<Order>(Some(&headers)) {
+                        Ok(order) => match validate(&order) {
+                            Ok(()) => {
+                                feed.flow.push(order);
+                                continue;
+                            }
+                            Err((field, message)) => FeedError::Row { file: file.to_string(), line, field: Some(field.to_string()), message },
+                        },
+                        Err(e) => row_error(file, e, &headers),
+                    }
+                } else {
+                    FeedError::Row { file: file.to_string(), line, field: None, message: format!("expected {} fields, found {}", headers.len(), record.len()) }
+                }
+            }
+            Err(e) => row_error(file, e, &headers),
         };
 
-        flow.push(record);
+        match (mode, error) {
+            (_, error @ FeedError::Io { .. }) | (Mode::Strict, error) => return Err(error),
+            (Mode::Lenient, error) => feed.skipped.push(error),
+        }
+    }
+
+    Ok(feed)
+}
+
+pub fn load_feed<P: AsRef<Path>>(path: P, mode: Mode) -> Result<Feed, FeedError> {
+    let file = path.as_ref().display().to_string();
+    let reader = File::open(&path).map_err(|error| FeedError::Io { file: file.clone(), error })?;
+    read_feed(io::BufReader::new(reader), &file, mode)
+}
+
+pub fn get_raw_feed() -> Result<Vec<Order>, FeedError> {
+    Ok(load_feed(SCORE_FEED, Mode::Strict)?.flow)
+}
+
+// Raw simulated order and cancel data feed
+// orders with price = 0 correspond to
+// cancels with orderid=size
+pub fn to_command(order: &Order) -> Command {
+    if order.price == 0 {
+        Command::Cancel(order.size)
+    } else {
+        Command::Limit(order.clone())
+    }
+}
+
+// The reverse of to_command, None for commands the legacy format cannot express.
+pub fn to_legacy(command: &Command) -> Option<Order> {
+    match command {
+        Command::Limit(order) => Some(order.clone()),
+        Command::Cancel(id) => Some(Order { symbol: String::new(), trader: String::new(), side: 0, price: 0, size: *id }),
+        _ => None,
+    }
+}
+
+pub fn write_feed<W: io::Write>(writer: W, flow: &[Order]) -> Result<(), csv::Error> {
+    let mut writer = csv::Writer::from_writer(writer);
+    for order in flow {
+        writer.serialize(order)?;
     }
+    writer.flush()?;
+    Ok(())
+}
 
-    flow
-}
\ No newline at end of file
+// Random access to a recorded flow, so playback reads csv, command and memory-mapped binary feeds alike.
+pub trait FlowSource {
+    fn messages(&self) -> usize;
+    fn command(&self, idx: usize) -> Command;
+}
+
+impl FlowSource for [Order] {
+    fn messages(&self) -> usize {
+        self.len()
+    }
+
+    fn command(&self, idx: usize) -> Command {
+        to_command(&self[idx])
+    }
+}
+
+impl FlowSource for [Command] {
+    fn messages(&self) -> usize {
+        self.len()
+    }
+
+    fn command(&self, idx: usize) -> Command {
+        self[idx].clone()
+    }
+}
+
+impl FlowSource for BinaryFeed {
+    fn messages(&self) -> usize {
+        self.len()
+    }
+
+    fn command(&self, idx: usize) -> Command {
+        let record = &self.records()[idx];
+        // Same convention as the csv, a cancel needs no strings built.
+        if record.price() == 0 {
+            Command::Cancel(record.size())
+        } else {
+            Command::Limit(record.to_order())
+        }
+    }
+}
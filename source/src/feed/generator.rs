@@ -0,0 +1,245 @@
+/*
+    Synthetic order flow from a seed, so engines can be tested and scored on many reproducible workloads.
+
+    Every symbol's mid price follows a random walk. Each message is then a cancel of one of the
+    generator's own outstanding orders (with probability cancel_ratio), a market order (market_ratio)
+    or a limit order. Limit orders are passive, resting up to depth levels behind the mid, or with
+    probability aggressiveness priced up to depth levels through it. Levels are spacing ticks apart.
+    With probability sweep_ratio a limit order is instead a sweep of sweep_size priced depth levels
+    through the mid.
+
+    depth only bounds how far from the mid orders are priced. With a target_depth the generator also
+    replays its feed into an engine and replaces cancel_ratio with half the ratio of resting orders to
+    the target, so cancels grow likelier above the target and rarer below it and the book settles there.
+
+    The engine keeps a single book, the symbol column only labels orders.
+*/
+
+use std::fmt;
+use crate::types::{Order, OrderId, Price, Size, Side};
+use crate::engine::command::Command;
+use crate::engine::engine::Engine;
+
+// SplitMix64, chosen over an external crate so a seed produces the same feed forever.
+pub struct Rng(u64);
+
+impl Rng {
+
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in [0, 1).
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    pub fn chance(&mut self, probability: f64) -> bool {
+        self.next_f64() < probability
+    }
+
+    // Uniform in [low, high].
+    pub fn range(&mut self, low: u64, high: u64) -> u64 {
+        low + self.next_u64() % (high - low + 1)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SizeDistribution {
+    Fixed(Size),
+    Uniform { min: Size, max: Size },
+    // lot times a geometric number of lots (at least one), p is the chance of stopping after each lot.
+    Geometric { lot: Size, p: f64 },
+}
+
+impl SizeDistribution {
+
+    pub fn sample(&self, rng: &mut Rng) -> Size {
+        match *self {
+            SizeDistribution::Fixed(size) => size,
+            SizeDistribution::Uniform { min, max } => rng.range(min, max),
+            SizeDistribution::Geometric { lot, p } => {
+                let mut lots = 1;
+                while !rng.chance(p) && lots < 10_000 {
+                    lots += 1;
+                }
+                lot * lots
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GeneratorConfig {
+    pub seed: u64,
+    pub messages: usize,
+    pub symbols: usize,
+    pub traders: usize,
+    // Starting mid of every symbol.
+    pub mid: Price,
+    // Chance per message that the mid moves one tick up or down.
+    pub volatility: f64,
+    pub sizes: SizeDistribution,
+    pub cancel_ratio: f64,
+    pub market_ratio: f64,
+    pub aggressiveness: f64,
+    // Price levels either side of the mid that orders are spread over.
+    pub depth: Price,
+    // Resting orders the book is steered towards, 0 to leave it to cancel_ratio.
+    pub target_depth: usize,
+    // Ticks between those levels.
+    pub spacing: Price,
+    pub sweep_ratio: f64,
+    pub sweep_size: Size,
+}
+
+impl GeneratorConfig {
+
+    // Roughly the shape of score_feed.csv.
+    pub fn new(seed: u64) -> GeneratorConfig {
+        GeneratorConfig {
+            seed,
+            messages: 35_000,
+            symbols: 1,
+            traders: 10,
+            mid: 4800,
+            volatility: 0.05,
+            sizes: SizeDistribution::Geometric { lot: 100, p: 0.3 },
+            cancel_ratio: 0.4,
+            market_ratio: 0.0,
+            aggressiveness: 0.1,
+            depth: 20,
+            target_depth: 0,
+            spacing: 1,
+            sweep_ratio: 0.0,
+            sweep_size: 10_000,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum GeneratorError {
+    // Orders depth levels of spacing ticks either side of the mid would not fit between 1 and Price::MAX.
+    Depth { depth: Price, spacing: Price },
+    // Uniform sizes with min above max, or spanning every u64 so Rng::range cannot draw from them.
+    Sizes { min: Size, max: Size },
+}
+
+impl fmt::Display for GeneratorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GeneratorError::Depth { depth, spacing } =>
+                write!(f, "depth {} times spacing {} must be below {}", depth, spacing, Price::MAX / 2),
+            GeneratorError::Sizes { min, max } =>
+                write!(f, "uniform sizes from {} to {} must have min at most max and not span every u64", min, max),
+        }
+    }
+}
+
+impl std::error::Error for GeneratorError {}
+
+impl GeneratorConfig {
+
+    pub fn validate(&self) -> Result<(), GeneratorError> {
+        if self.reach() >= (Price::MAX / 2) as u32 {
+            return Err(GeneratorError::Depth { depth: self.depth, spacing: self.spacing });
+        }
+        if let SizeDistribution::Uniform { min, max } = self.sizes {
+            if min > max || max - min == u64::MAX {
+                return Err(GeneratorError::Sizes { min, max });
+            }
+        }
+        Ok(())
+    }
+
+    // Ticks from the mid to the furthest level, in u32 as it may not fit a Price.
+    fn reach(&self) -> u32 {
+        self.depth.max(1) as u32 * self.spacing.max(1) as u32
+    }
+}
+
+pub fn generate(config: &GeneratorConfig) -> Result<Vec<Command>, GeneratorError> {
+    config.validate()?;
+    let mut rng = Rng::new(config.seed);
+    let depth = config.depth.max(1);
+    let spacing = config.spacing.max(1);
+    // Below Price::MAX / 2 once validated, so every price lies in [1, Price::MAX - 1].
+    let reach = config.reach() as Price;
+    let (low, high) = (reach + 1, Price::MAX - reach - 1);
+
+    let mut mids: Vec<Price> = vec![config.mid.max(low).min(high); config.symbols.max(1)];
+    let mut outstanding: Vec<OrderId> = Vec::new();
+    let mut next_id: OrderId = 1;
+    let mut commands = Vec::with_capacity(config.messages);
+    // Only built for a target, so feeds without one cost and draw the same as before.
+    let mut shadow = if config.target_depth > 0 { Some(Engine::new()) } else { None };
+
+    for _ in 0..config.messages {
+        let symbol = rng.range(0, mids.len() as u64 - 1) as usize;
+        if rng.chance(config.volatility) {
+            let mid = &mut mids[symbol];
+            *mid = if rng.chance(0.5) { (*mid + 1).min(high) } else { (*mid - 1).max(low) };
+        }
+
+        let cancel_ratio = match &shadow {
+            Some(engine) => {
+                let resting = engine.book(0).len() + engine.book(1).len();
+                (resting as f64 / (2 * config.target_depth) as f64).min(0.95)
+            }
+            None => config.cancel_ratio,
+        };
+        if !outstanding.is_empty() && rng.chance(cancel_ratio) {
+            let pick = rng.range(0, outstanding.len() as u64 - 1) as usize;
+            push(&mut commands, &mut shadow, Command::Cancel(outstanding.swap_remove(pick)));
+            continue;
+        }
+
+        let trader = rng.range(0, config.traders.max(1) as u64 - 1) as usize;
+        let side: Side = rng.range(0, 1) as Side;
+        let mut order = Order {
+            symbol: format!("SYM{}", symbol),
+            trader: format!("ID{}", trader),
+            side,
+            price: 0,
+            size: config.sizes.sample(&mut rng).max(1),
+        };
+
+        if rng.chance(config.market_ratio) {
+            push(&mut commands, &mut shadow, Command::Market(order));
+        } else {
+            let mid = mids[symbol];
+            // Drawn only when enabled so feeds without sweeps keep their seeds.
+            let sweep = config.sweep_ratio > 0.0 && rng.chance(config.sweep_ratio);
+            let offset = if sweep { reach } else { rng.range(1, depth as u64) as Price * spacing };
+            if sweep {
+                order.size = config.sweep_size.max(1);
+            }
+            // Bids rest below the mid and asks above unless aggressive.
+            let through = sweep || rng.chance(config.aggressiveness);
+            order.price = match (side == 0, through) {
+                (true, false) | (false, true) => mid - offset,
+                (true, true) | (false, false) => mid + offset,
+            };
+            outstanding.push(next_id);
+            push(&mut commands, &mut shadow, Command::Limit(order));
+        }
+        next_id += 1;
+    }
+
+    Ok(commands)
+}
+
+fn push(commands: &mut Vec<Command>, shadow: &mut Option<Engine>, command: Command) {
+    if let Some(engine) = shadow {
+        engine.apply(command.clone());
+    }
+    commands.push(command);
+}
@@ -0,0 +1,144 @@
+/*
+    The C initialiser form of a feed used by cpp_reference, one order per line
+        {"SYM", "ID8", 0, 4799, 500},
+
+    score_feed.h wraps the lines in the raw_feed[] declaration, parsed_feed.txt is the bare lines.
+    Lines not starting with '{' (includes, comments, the declaration) are ignored when reading.
+*/
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use crate::types::Order;
+use crate::feed::feed::{Feed, FeedError, Mode};
+
+// Everything score_feed.h has before the first order.
+pub const HEADER_PREFIX: &str = "#include \"types.h\"\n\n\
+/* Raw simulated order and cancel data feed\n   \
+orders with price = 0 correspond to \n   \
+cancels with orderid=size */\n\n\
+t_order raw_feed[] = {\n";
+pub const HEADER_SUFFIX: &str = "  };\n\n";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Layout {
+    // Bare initialiser lines, as parsed_feed.txt.
+    Lines,
+    // A complete header declaring raw_feed[], as score_feed.h.
+    Header,
+}
+
+// One row where two feeds disagree. left or right is None past the end of the shorter feed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Difference {
+    pub index: usize,
+    pub left: Option<Order>,
+    pub right: Option<Order>,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |order: &Option<Order>| order.as_ref().map(|x| x.to_string()).unwrap_or_else(|| String::from("(missing)"));
+        write!(f, "order {}: {} != {}", self.index, show(&self.left), show(&self.right))
+    }
+}
+
+fn name(field: &str) -> Option<String> {
+    let inner = field.strip_prefix('"')?.strip_suffix('"')?;
+    if inner.contains('"') {
+        return None;
+    }
+    Some(inner.to_string())
+}
+
+fn number<T: std::str::FromStr>(value: &str, field: &'static str) -> Result<T, (Option<&'static str>, String)>
+where T::Err: fmt::Display {
+    value.parse().map_err(|e| (Some(field), format!("{}: {}", value, e)))
+}
+
+fn parse_row(line: &str) -> Result<Order, (Option<&'static str>, String)> {
+    let body = line.strip_prefix('{')
+        .and_then(|x| x.strip_suffix("},").or_else(|| x.strip_suffix('}')))
+        .ok_or((None, String::from("expected {...},")))?;
+
+    let fields: Vec<&str> = body.split(',').map(|x| x.trim()).collect();
+    if fields.len() != 5 {
+        return Err((None, format!("expected 5 fields, found {}", fields.len())));
+    }
+
+    let quoted = |idx: usize, field| name(fields[idx]).ok_or((Some(field), format!("{} is not a quoted string", fields[idx])));
+
+    let order = Order {
+        symbol: quoted(0, "symbol")?,
+        trader: quoted(1, "trader")?,
+        side: number(fields[2], "side")?,
+        price: number(fields[3], "price")?,
+        size: number(fields[4], "size")?,
+    };
+    if order.side > 1 {
+        return Err((Some("side"), format!("{} is neither 0 (bid) nor 1 (ask)", order.side)));
+    }
+    Ok(order)
+}
+
+// Reads the orders of either layout. file names the source in errors.
+pub fn read_initialisers<R: BufRead>(reader: R, file: &str, mode: Mode) -> Result<Feed, FeedError> {
+    let mut feed = Feed { flow: Vec::new(), skipped: Vec::new() };
+
+    for (idx, line) in reader.lines().enumerate() {
+        let line = line.map_err(|error| FeedError::Io { file: file.to_string(), error })?;
+        let line = line.trim();
+        if !line.starts_with('{') {
+            continue;
+        }
+
+        match parse_row(line) {
+            Ok(order) => feed.flow.push(order),
+            Err((field, message)) => {
+                let error = FeedError::Row { file: file.to_string(), line: idx as u64 + 1, field: field.map(String::from), message };
+                match mode {
+                    Mode::Strict => return Err(error),
+                    Mode::Lenient => feed.skipped.push(error),
+                }
+            }
+        }
+    }
+
+    Ok(feed)
+}
+
+pub fn load_initialisers<P: AsRef<Path>>(path: P, mode: Mode) -> Result<Feed, FeedError> {
+    let file = path.as_ref().display().to_string();
+    let reader = File::open(&path).map_err(|error| FeedError::Io { file: file.clone(), error })?;
+    read_initialisers(io::BufReader::new(reader), &file, mode)
+}
+
+pub fn write_initialisers<W: Write>(writer: W, flow: &[Order], layout: Layout) -> io::Result<()> {
+    let mut writer = io::BufWriter::new(writer);
+
+    if layout == Layout::Header {
+        writer.write_all(HEADER_PREFIX.as_bytes())?;
+    }
+    for order in flow {
+        for name in &[&order.symbol, &order.trader] {
+            if name.contains('"') || name.contains('\\') {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("'{}' cannot be written as a C string", name)));
+            }
+        }
+        writeln!(writer, "{{\"{}\", \"{}\", {}, {}, {}}},", order.symbol, order.trader, order.side, order.price, order.size)?;
+    }
+    if layout == Layout::Header {
+        writer.write_all(HEADER_SUFFIX.as_bytes())?;
+    }
+
+    writer.flush()
+}
+
+// Every position where the two flows differ, in order.
+pub fn compare(left: &[Order], right: &[Order]) -> Vec<Difference> {
+    (0..left.len().max(right.len()))
+        .filter(|idx| left.get(*idx) != right.get(*idx))
+        .map(|idx| Difference { index: idx, left: left.get(idx).cloned(), right: right.get(idx).cloned() })
+        .collect()
+}
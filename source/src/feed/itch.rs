@@ -0,0 +1,343 @@
+/*
+    Decoder for ITCH 5.0 style captures, so the engine can be driven by real exchange order flow.
+
+    A capture is a stream of messages, each preceded by its length as a big endian u16 (the framing of
+    the exchange's historical files). Every message starts with
+        type: u8 | stock locate: u16 | tracking number: u16 | timestamp: u48
+    followed by (big endian, prices have 4 implied decimals)
+        A  add order            reference: u64 | side: 'B'/'S' | shares: u32 | stock: [u8; 8] | price: u32
+        F  add with attribution as A, then attribution: [u8; 4]
+        E  order executed       reference: u64 | shares: u32 | match number: u64
+        X  order cancel         reference: u64 | shares: u32
+        D  order delete         reference: u64
+        U  order replace        reference: u64 | new reference: u64 | shares: u32 | price: u32
+    Other message types are kept undecoded and produce no command.
+
+    An order is followed by the id the engine gave it, so the engine's answer to every command is passed
+    back to the Translator. to_commands replays the commands into an engine of its own as it translates,
+    which a fresh engine playing them back then repeats. An order the engine rejected, like a replace
+    whose original had already traded away, is forgotten, and one that traded on entry is followed at
+    the size left resting. Executions and partial cancels reduce the resting order, the aggressors
+    behind executions are not in the feed.
+*/
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use crate::types::{Order, OrderId, Price, Side};
+use crate::engine::command::{Applied, Command};
+use crate::engine::engine::Engine;
+
+pub const ADD: u8 = b'A';
+pub const ADD_ATTRIBUTED: u8 = b'F';
+pub const EXECUTED: u8 = b'E';
+pub const CANCEL: u8 = b'X';
+pub const DELETE: u8 = b'D';
+pub const REPLACE: u8 = b'U';
+
+// type, stock locate, tracking number and timestamp.
+const HEADER_SIZE: usize = 11;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Message {
+    Add { reference: u64, side: Side, shares: u32, stock: String, price: u32, attribution: Option<String> },
+    Executed { reference: u64, shares: u32, match_number: u64 },
+    Cancel { reference: u64, shares: u32 },
+    Delete { reference: u64 },
+    Replace { reference: u64, new_reference: u64, shares: u32, price: u32 },
+    // Any other message, as it appeared in the capture.
+    Other(Vec<u8>),
+}
+
+#[derive(Debug)]
+pub enum ItchError {
+    Io(io::Error),
+    // The capture ends inside a frame.
+    Truncated { offset: usize },
+    // A message whose length does not match its type.
+    Length { offset: usize, kind: char, length: usize },
+    Side { offset: usize, value: u8 },
+    // A price that is not a whole number of ticks in the engine's price range.
+    Price { reference: u64, price: u32 },
+}
+
+impl fmt::Display for ItchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ItchError::Io(e) => write!(f, "itch io error: {}", e),
+            ItchError::Truncated { offset } => write!(f, "offset {}: capture ends inside a message", offset),
+            ItchError::Length { offset, kind, length } => write!(f, "offset {}: {} bytes is not a valid '{}' message", offset, length, kind),
+            ItchError::Side { offset, value } => write!(f, "offset {}: side {:?} is neither 'B' nor 'S'", offset, *value as char),
+            ItchError::Price { reference, price } => write!(f, "order {}: price {} does not fit the engine's ticks", reference, price),
+        }
+    }
+}
+
+impl std::error::Error for ItchError {}
+
+impl From<io::Error> for ItchError {
+    fn from(e: io::Error) -> Self {
+        ItchError::Io(e)
+    }
+}
+
+fn length(kind: u8) -> Option<usize> {
+    match kind {
+        ADD => Some(36),
+        ADD_ATTRIBUTED => Some(40),
+        EXECUTED => Some(31),
+        CANCEL => Some(23),
+        DELETE => Some(19),
+        REPLACE => Some(35),
+        _ => None,
+    }
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+fn u64_at(bytes: &[u8], at: usize) -> u64 {
+    let mut field = [0u8; 8];
+    field.copy_from_slice(&bytes[at..at + 8]);
+    u64::from_be_bytes(field)
+}
+
+// Alpha fields are space padded on the right.
+fn alpha(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).trim_end().to_string()
+}
+
+fn pack_alpha(text: &str, out: &mut Vec<u8>, width: usize) {
+    let bytes = text.as_bytes();
+    out.extend(bytes.iter().take(width));
+    out.resize(out.len() + width.saturating_sub(bytes.len()), b' ');
+}
+
+impl Message {
+
+    // offset locates the message in the capture for errors.
+    pub fn decode(bytes: &[u8], offset: usize) -> Result<Message, ItchError> {
+        let kind = match bytes.first() {
+            Some(kind) => *kind,
+            None => return Err(ItchError::Length { offset, kind: '?', length: 0 }),
+        };
+        match length(kind) {
+            None => return Ok(Message::Other(bytes.to_vec())),
+            Some(expected) if expected != bytes.len() => return Err(ItchError::Length { offset, kind: kind as char, length: bytes.len() }),
+            Some(_) => {}
+        }
+
+        let reference = u64_at(bytes, HEADER_SIZE);
+        let message = match kind {
+            ADD | ADD_ATTRIBUTED => Message::Add {
+                reference,
+                side: match bytes[19] {
+                    b'B' => 0,
+                    b'S' => 1,
+                    value => return Err(ItchError::Side { offset, value }),
+                },
+                shares: u32_at(bytes, 20),
+                stock: alpha(&bytes[24..32]),
+                price: u32_at(bytes, 32),
+                attribution: if kind == ADD_ATTRIBUTED { Some(alpha(&bytes[36..40])) } else { None },
+            },
+            EXECUTED => Message::Executed { reference, shares: u32_at(bytes, 19), match_number: u64_at(bytes, 23) },
+            CANCEL => Message::Cancel { reference, shares: u32_at(bytes, 19) },
+            DELETE => Message::Delete { reference },
+            _ => Message::Replace { reference, new_reference: u64_at(bytes, 19), shares: u32_at(bytes, 27), price: u32_at(bytes, 31) },
+        };
+        Ok(message)
+    }
+
+    // Appends the framed message, with zero stock locate, tracking number and timestamp.
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        let mut body = Vec::with_capacity(40);
+        let header = |body: &mut Vec<u8>, kind: u8, reference: u64| {
+            body.push(kind);
+            body.extend(&[0u8; HEADER_SIZE - 1]);
+            body.extend(&reference.to_be_bytes());
+        };
+
+        match self {
+            Message::Add { reference, side, shares, stock, price, attribution } => {
+                header(&mut body, if attribution.is_some() { ADD_ATTRIBUTED } else { ADD }, *reference);
+                body.push(if *side == 0 { b'B' } else { b'S' });
+                body.extend(&shares.to_be_bytes());
+                pack_alpha(stock, &mut body, 8);
+                body.extend(&price.to_be_bytes());
+                if let Some(attribution) = attribution {
+                    pack_alpha(attribution, &mut body, 4);
+                }
+            }
+            Message::Executed { reference, shares, match_number } => {
+                header(&mut body, EXECUTED, *reference);
+                body.extend(&shares.to_be_bytes());
+                body.extend(&match_number.to_be_bytes());
+            }
+            Message::Cancel { reference, shares } => {
+                header(&mut body, CANCEL, *reference);
+                body.extend(&shares.to_be_bytes());
+            }
+            Message::Delete { reference } => header(&mut body, DELETE, *reference),
+            Message::Replace { reference, new_reference, shares, price } => {
+                header(&mut body, REPLACE, *reference);
+                body.extend(&new_reference.to_be_bytes());
+                body.extend(&shares.to_be_bytes());
+                body.extend(&price.to_be_bytes());
+            }
+            Message::Other(bytes) => body.extend(bytes),
+        }
+
+        out.extend(&(body.len() as u16).to_be_bytes());
+        out.extend(body);
+    }
+}
+
+pub fn decode(capture: &[u8]) -> Result<Vec<Message>, ItchError> {
+    let mut messages = Vec::new();
+    let mut offset = 0;
+
+    while offset < capture.len() {
+        if offset + 2 > capture.len() {
+            return Err(ItchError::Truncated { offset });
+        }
+        let length = u16::from_be_bytes([capture[offset], capture[offset + 1]]) as usize;
+        let end = offset + 2 + length;
+        if end > capture.len() {
+            return Err(ItchError::Truncated { offset });
+        }
+
+        messages.push(Message::decode(&capture[offset + 2..end], offset)?);
+        offset = end;
+    }
+
+    Ok(messages)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ItchConfig {
+    // Only orders in this stock are kept, the engine has a single book.
+    pub stock: Option<String>,
+    // ITCH price units (1/10000) per engine price tick.
+    pub tick: u32,
+}
+
+impl ItchConfig {
+
+    // Every stock, prices in cents.
+    pub fn new() -> ItchConfig {
+        ItchConfig { stock: None, tick: 100 }
+    }
+}
+
+impl Default for ItchConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct Resting {
+    id: OrderId,
+    // Size is what remains on the book.
+    order: Order,
+}
+
+// Follows the orders of a capture and turns messages into engine commands.
+pub struct Translator {
+    config: ItchConfig,
+    // The order of the last command, entered under its reference once the engine gives it an id.
+    pending: Option<(u64, Order)>,
+    orders: HashMap<u64, Resting>,
+}
+
+impl Translator {
+
+    pub fn new(config: ItchConfig) -> Translator {
+        Translator { config, pending: None, orders: HashMap::new() }
+    }
+
+    fn price(&self, reference: u64, price: u32) -> Result<Price, ItchError> {
+        let tick = self.config.tick.max(1);
+        let ticks = price / tick;
+        if !price.is_multiple_of(tick) || ticks == 0 || ticks > Price::MAX as u32 {
+            return Err(ItchError::Price { reference, price });
+        }
+        Ok(ticks as Price)
+    }
+
+    // What the engine answered to the command last returned by translate, and the engine after it.
+    pub fn applied(&mut self, applied: &Applied, engine: &Engine) {
+        if let (Some((reference, order)), Applied::Order(Ok(id))) = (self.pending.take(), applied) {
+            // Whatever traded on entry is gone, an order that filled completely is not followed.
+            if let Some(resting) = engine.resting(*id) {
+                self.orders.insert(reference, Resting { id: *id, order: Order { size: resting.size, ..order } });
+            }
+        }
+    }
+
+    // Removes shares from an order, cancelling it once nothing is left.
+    fn reduce(&mut self, reference: u64, shares: u32) -> Option<Command> {
+        let resting = self.orders.get_mut(&reference)?;
+        resting.order.size = resting.order.size.saturating_sub(shares as u64);
+        if resting.order.size > 0 {
+            return Some(Command::Amend { id: resting.id, size: resting.order.size });
+        }
+        self.orders.remove(&reference).map(|x| Command::Cancel(x.id))
+    }
+
+    // None for messages with no effect on the book, including those for orders never entered
+    // (other stocks, added before the capture started or rejected by the engine).
+    pub fn translate(&mut self, message: &Message) -> Result<Option<Command>, ItchError> {
+        self.pending = None;
+        let command = match message {
+            Message::Add { reference, side, shares, stock, price, attribution } => {
+                if self.config.stock.as_ref().is_some_and(|x| x != stock) {
+                    return Ok(None);
+                }
+                let order = Order {
+                    symbol: stock.clone(),
+                    trader: attribution.clone().unwrap_or_default(),
+                    side: *side,
+                    price: self.price(*reference, *price)?,
+                    size: *shares as u64,
+                };
+                self.pending = Some((*reference, order.clone()));
+                Some(Command::Limit(order))
+            }
+            Message::Executed { reference, shares, .. } | Message::Cancel { reference, shares } => self.reduce(*reference, *shares),
+            Message::Delete { reference } => self.orders.remove(reference).map(|x| Command::Cancel(x.id)),
+            Message::Replace { reference, new_reference, shares, price } => match self.orders.remove(reference) {
+                None => None,
+                Some(resting) => {
+                    let order = Order { price: self.price(*new_reference, *price)?, size: *shares as u64, ..resting.order };
+                    self.pending = Some((*new_reference, order.clone()));
+                    Some(Command::Replace { id: resting.id, order })
+                }
+            },
+            Message::Other(_) => None,
+        };
+        Ok(command)
+    }
+}
+
+pub fn to_commands(messages: &[Message], config: &ItchConfig) -> Result<Vec<Command>, ItchError> {
+    let mut translator = Translator::new(config.clone());
+    let mut engine = Engine::new();
+    let mut commands = Vec::new();
+    for message in messages {
+        if let Some(command) = translator.translate(message)? {
+            let applied = engine.apply(command.clone());
+            translator.applied(&applied, &engine);
+            commands.push(command);
+        }
+    }
+    Ok(commands)
+}
+
+// Decodes a capture file into commands ready for playback or replay.
+pub fn load_capture<P: AsRef<Path>>(path: P, config: &ItchConfig) -> Result<Vec<Command>, ItchError> {
+    to_commands(&decode(&fs::read(path)?)?, config)
+}
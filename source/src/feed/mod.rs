@@ -1 +1,7 @@
-pub mod feed;
\ No newline at end of file
+pub mod feed;
+pub mod binary;
+pub mod commands;
+pub mod generator;
+pub mod initialiser;
+pub mod itch;
+mod test;
@@ -0,0 +1,467 @@
+#[cfg(test)]
+mod feed_tests {
+
+    use std::fs;
+    use std::path::PathBuf;
+    use crate::types::Order;
+    use crate::feed::feed::{get_raw_feed, load_feed, read_feed, to_legacy, write_feed, FlowSource, FeedError, Mode};
+    use crate::feed::binary::{BinaryFeed, BinaryFeedError, convert_csv, write_binary_feed, HEADER_SIZE, RECORD_SIZE};
+    use crate::feed::commands::{read_commands, write_commands, Version};
+    use crate::feed::generator::{generate, GeneratorConfig, GeneratorError, SizeDistribution};
+    use crate::feed::itch::{decode, load_capture, to_commands, ItchConfig, ItchError, Message};
+    use crate::feed::initialiser::{compare, load_initialisers, read_initialisers, write_initialisers, Difference, Layout};
+    use crate::engine::command::Command;
+    use crate::engine::engine::Engine;
+    use crate::score::score::playback;
+
+    fn scratch_file(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("orderbook_feed_{}_{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_binary_round_trip() {
+        let path = scratch_file("round_trip.bin");
+        let flow = get_raw_feed().unwrap();
+
+        assert_eq!(convert_csv("./data/score_feed.csv", &path).unwrap(), flow.len());
+        let binary = BinaryFeed::open(&path).unwrap();
+
+        assert_eq!(fs::metadata(&path).unwrap().len() as usize, HEADER_SIZE + flow.len() * RECORD_SIZE);
+        assert_eq!(binary.messages(), flow.len());
+        for idx in 0..flow.len() {
+            assert_eq!(binary.command(idx), flow.command(idx));
+        }
+        assert_eq!(&binary.records()[0].to_order(), &flow[0]);
+
+        playback(&binary);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_binary_rejects_long_names() {
+        let path = scratch_file("long.bin");
+        let order = Order {symbol: String::from("SYM"), trader: String::from("TRADER_NAME"), side: 0, price: 1, size: 1};
+
+        let written = write_binary_feed(&path, &[order]);
+        let _ = fs::remove_file(&path);
+        assert!(matches!(written, Err(BinaryFeedError::NameTooLong { record: 0, .. })));
+    }
+
+    #[test]
+    fn test_binary_rejects_bad_files() {
+        let path = scratch_file("bad.bin");
+
+        fs::write(&path, b"symbol,trader,side,price,size\nSYM,ID1,0,1,1\n").unwrap();
+        assert!(matches!(BinaryFeed::open(&path), Err(BinaryFeedError::Header(_))));
+
+        // Truncated body.
+        let order = Order {symbol: String::from("SYM"), trader: String::from("ID1"), side: 0, price: 1, size: 1};
+        write_binary_feed(&path, &[order.clone(), order]).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+        assert!(matches!(BinaryFeed::open(&path), Err(BinaryFeedError::Header(_))));
+
+        // A record count whose size wraps around to the file's length.
+        let mut oversized = bytes.clone();
+        let wrapping = (1u64 << 59) + 2;
+        assert_eq!((wrapping as usize).wrapping_mul(RECORD_SIZE), 2 * RECORD_SIZE);
+        oversized[16..24].copy_from_slice(&wrapping.to_le_bytes());
+        fs::write(&path, &oversized).unwrap();
+        assert!(matches!(BinaryFeed::open(&path), Err(BinaryFeedError::Header(_))));
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    const DAMAGED: &str = "symbol,trader,side,price,size\n\
+        SYM,ID1,0,100,10\n\
+        SYM,ID2,0,abc,10\n\
+        SYM,ID3,1,101,10,9\n\
+        SYM,ID4,2,101,10\n\
+        SYM,ID5,1,101,5\n";
+
+    fn row(error: &FeedError) -> (u64, Option<&str>) {
+        match error {
+            FeedError::Row { line, field, .. } => (*line, field.as_deref()),
+            other => panic!("expected a row error, got {}", other),
+        }
+    }
+
+    #[test]
+    fn test_score_feed_is_clean() {
+        let feed = load_feed("./data/score_feed.csv", Mode::Lenient).unwrap();
+        assert!(feed.skipped.is_empty());
+        assert_eq!(feed.flow.len(), 35759);
+    }
+
+    #[test]
+    fn test_trailing_comma() {
+        let feed = read_feed("symbol,trader,side,price,size\nSYM,ID1,0,100,10,\nSYM,ID2,1,101,10".as_bytes(), "comma.csv", Mode::Strict).unwrap();
+        assert_eq!(feed.flow, vec![sym("ID1", 0, 100, 10), sym("ID2", 1, 101, 10)]);
+    }
+
+    #[test]
+    fn test_strict_reports_first_bad_row() {
+        match read_feed(DAMAGED.as_bytes(), "damaged.csv", Mode::Strict) {
+            Err(error) => {
+                assert_eq!(row(&error), (3, Some("price")));
+                assert!(error.to_string().starts_with("damaged.csv:3: field 'price'"));
+            }
+            Ok(_) => panic!("damaged feed loaded"),
+        }
+    }
+
+    #[test]
+    fn test_lenient_skips_and_reports() {
+        let feed = read_feed(DAMAGED.as_bytes(), "damaged.csv", Mode::Lenient).unwrap();
+
+        let traders: Vec<&str> = feed.flow.iter().map(|x| x.trader.as_str()).collect();
+        assert_eq!(traders, vec!["ID1", "ID5"]);
+
+        let skipped: Vec<(u64, Option<&str>)> = feed.skipped.iter().map(row).collect();
+        assert_eq!(skipped, vec![(3, Some("price")), (4, None), (5, Some("side"))]);
+    }
+
+    #[test]
+    fn test_missing_feed() {
+        assert!(matches!(load_feed("./data/no_such_feed.csv", Mode::Lenient), Err(FeedError::Io { .. })));
+    }
+
+    const V2: &str = "action,id,symbol,trader,side,price,size\n\
+        new,,SYM,ID1,0,100,10\n\
+        market,,SYM,ID2,1,,5\n\
+        amend,1,,,,,3\n\
+        replace,1,SYM,ID1,0,101,20\n\
+        cancel,4,,,,,\n";
+
+    fn sym(trader: &str, side: u8, price: u16, size: u64) -> Order {
+        Order {symbol: String::from("SYM"), trader: String::from(trader), side, price, size}
+    }
+
+    #[test]
+    fn test_read_v2_commands() {
+        let feed = read_commands(V2.as_bytes(), "v2.csv", Mode::Strict).unwrap();
+
+        assert_eq!(feed.version, Version::V2);
+        assert_eq!(feed.commands, vec![
+            Command::Limit(sym("ID1", 0, 100, 10)),
+            Command::Market(sym("ID2", 1, 0, 5)),
+            Command::Amend { id: 1, size: 3 },
+            Command::Replace { id: 1, order: sym("ID1", 0, 101, 20) },
+            Command::Cancel(4),
+        ]);
+
+        // Round trips through the writer.
+        let mut written = Vec::new();
+        write_commands(&mut written, &feed.commands).unwrap();
+        assert_eq!(read_commands(written.as_slice(), "written.csv", Mode::Strict).unwrap().commands, feed.commands);
+    }
+
+    #[test]
+    fn test_read_v2_errors() {
+        let damaged = "action,id,symbol,trader,side,price,size\n\
+            new,,SYM,ID1,0,,10\n\
+            cancel,,,,,,\n\
+            fill,1,,,,,\n\
+            amend,1,,,,,x\n\
+            cancel,1,,,,,\n";
+
+        let feed = read_commands(damaged.as_bytes(), "damaged.csv", Mode::Lenient).unwrap();
+        assert_eq!(feed.commands, vec![Command::Cancel(1)]);
+
+        let skipped: Vec<(u64, Option<&str>)> = feed.skipped.iter().map(row).collect();
+        assert_eq!(skipped, vec![(2, Some("price")), (3, Some("id")), (4, Some("action")), (5, Some("size"))]);
+        assert!(feed.skipped[3].to_string().starts_with("damaged.csv:5: field 'size'"));
+
+        assert!(read_commands("side,price\n".as_bytes(), "unknown.csv", Mode::Lenient).is_err());
+    }
+
+    #[test]
+    fn test_read_legacy_commands() {
+        let feed = read_commands(std::io::BufReader::new(fs::File::open("./data/score_feed.csv").unwrap()), "score_feed.csv", Mode::Strict).unwrap();
+        let flow = get_raw_feed().unwrap();
+
+        assert_eq!(feed.version, Version::Legacy);
+        assert_eq!(feed.commands.len(), flow.len());
+        assert_eq!(feed.commands[0], Command::Limit(flow[0].clone()));
+        assert_eq!(feed.commands[2], Command::Cancel(2));
+    }
+
+    #[test]
+    fn test_generator_is_seeded() {
+        let config = GeneratorConfig { messages: 2000, ..GeneratorConfig::new(7) };
+
+        assert_eq!(generate(&config).unwrap(), generate(&config).unwrap());
+        assert_ne!(generate(&config).unwrap(), generate(&GeneratorConfig { seed: 8, ..config.clone() }).unwrap());
+    }
+
+    #[test]
+    fn test_generator_shape() {
+        let config = GeneratorConfig { messages: 10_000, symbols: 3, depth: 5, volatility: 0.0, ..GeneratorConfig::new(1) };
+        let commands = generate(&config).unwrap();
+        assert_eq!(commands.len(), config.messages);
+
+        let cancels = commands.iter().filter(|x| matches!(x, Command::Cancel(_))).count() as f64;
+        assert!((cancels / commands.len() as f64 - config.cancel_ratio).abs() < 0.05);
+
+        // Only the generator's own orders are cancelled, each at most once.
+        let mut cancelled = std::collections::HashSet::new();
+        let mut entered = 0;
+        for command in &commands {
+            match command {
+                Command::Limit(order) => {
+                    entered += 1;
+                    assert!(order.price >= config.mid - 5 && order.price <= config.mid + 5 && order.price != config.mid);
+                }
+                Command::Cancel(id) => assert!(*id <= entered && cancelled.insert(*id)),
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_generator_target_depth() {
+        for &target in &[100, 2000] {
+            let config = GeneratorConfig { messages: 40_000, target_depth: target, ..GeneratorConfig::new(3) };
+            let mut engine = Engine::new();
+            let mut samples = Vec::new();
+            for (i, command) in generate(&config).unwrap().into_iter().enumerate() {
+                engine.apply(command);
+                // Past the ramp up the book holds around the target.
+                if i >= 20_000 && i % 100 == 0 {
+                    samples.push(engine.book(0).len() + engine.book(1).len());
+                }
+            }
+            let mean = samples.iter().sum::<usize>() as f64 / samples.len() as f64;
+            assert!((mean / target as f64 - 1.0).abs() < 0.2, "target {} mean {}", target, mean);
+        }
+    }
+
+    #[test]
+    fn test_generator_rejects_bad_configs() {
+        let config = GeneratorConfig { messages: 2000, ..GeneratorConfig::new(1) };
+        let rejected = |depth, spacing| generate(&GeneratorConfig { depth, spacing, ..config.clone() }) == Err(GeneratorError::Depth { depth, spacing });
+
+        assert!(rejected(u16::MAX / 2, 1));
+        assert!(rejected(u16::MAX, 1));
+        // Would overflow multiplying the offset, and adding it to the mid.
+        assert!(rejected(2000, 40));
+        assert!(rejected(40_000, 1));
+
+        // Uniform sizes Rng::range cannot draw from.
+        for (min, max) in [(10, 5), (0, u64::MAX)] {
+            let sizes = SizeDistribution::Uniform { min, max };
+            assert_eq!(generate(&GeneratorConfig { sizes, ..config.clone() }), Err(GeneratorError::Sizes { min, max }));
+        }
+        assert!(generate(&GeneratorConfig { sizes: SizeDistribution::Uniform { min: 5, max: 5 }, ..config.clone() }).is_ok());
+
+        // The widest reach allowed still prices every order inside the range.
+        for (depth, spacing) in [(u16::MAX / 2 - 1, 1), (1000, 32)] {
+            for command in generate(&GeneratorConfig { depth, spacing, ..config.clone() }).unwrap() {
+                if let Command::Limit(order) = command {
+                    assert!(order.price > 0 && order.price < u16::MAX);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_generated_feed_round_trip() {
+        let commands = generate(&GeneratorConfig { messages: 5000, market_ratio: 0.05, ..GeneratorConfig::new(3) }).unwrap();
+
+        let mut written = Vec::new();
+        write_commands(&mut written, &commands).unwrap();
+        let feed = read_commands(written.as_slice(), "generated.csv", Mode::Strict).unwrap();
+        assert_eq!(feed.commands, commands);
+        playback(feed.commands.as_slice());
+
+        // Without market orders the legacy format holds the same flow.
+        let commands = generate(&GeneratorConfig { messages: 5000, ..GeneratorConfig::new(3) }).unwrap();
+        let flow: Vec<Order> = commands.iter().filter_map(to_legacy).collect();
+        let mut written = Vec::new();
+        write_feed(&mut written, &flow).unwrap();
+        assert_eq!(read_commands(written.as_slice(), "legacy.csv", Mode::Strict).unwrap().commands, commands);
+    }
+
+    const SCORE_FEED_H: &str = "../cpp_reference/score_feed.h";
+    const PARSED_FEED: &str = "../cpp_reference/parsed_feed.txt";
+
+    #[test]
+    fn test_c_feeds_match_csv() {
+        let flow = get_raw_feed().unwrap();
+
+        for path in &[SCORE_FEED_H, PARSED_FEED] {
+            let feed = load_initialisers(path, Mode::Strict).unwrap();
+            assert_eq!(compare(&feed.flow, &flow), vec![], "{}", path);
+        }
+
+        // Writing the csv back out reproduces both files exactly.
+        for (path, layout) in &[(SCORE_FEED_H, Layout::Header), (PARSED_FEED, Layout::Lines)] {
+            let mut written = Vec::new();
+            write_initialisers(&mut written, &flow, *layout).unwrap();
+            assert!(written == fs::read(path).unwrap(), "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_c_feed_errors() {
+        let damaged = "t_order raw_feed[] = {\n\
+            {\"SYM\", \"ID1\", 0, 100, 10},\n\
+            {\"SYM\", ID2, 0, 100, 10},\n\
+            {\"SYM\", \"ID3\", 0, 100},\n\
+            {\"SYM\", \"ID4\", 0, 70000, 10},\n\
+            };\n";
+
+        assert!(read_initialisers(damaged.as_bytes(), "damaged.h", Mode::Strict).is_err());
+
+        let feed = read_initialisers(damaged.as_bytes(), "damaged.h", Mode::Lenient).unwrap();
+        assert_eq!(feed.flow, vec![Order {symbol: String::from("SYM"), trader: String::from("ID1"), side: 0, price: 100, size: 10}]);
+        let skipped: Vec<(u64, Option<&str>)> = feed.skipped.iter().map(row).collect();
+        assert_eq!(skipped, vec![(3, Some("trader")), (4, None), (5, Some("price"))]);
+    }
+
+    #[test]
+    fn test_compare_reports_differences() {
+        let flow = get_raw_feed().unwrap();
+        let mut changed = flow[..100].to_vec();
+        changed[10].size += 1;
+
+        assert_eq!(compare(&flow[..101], &changed), vec![
+            Difference { index: 10, left: Some(flow[10].clone()), right: Some(changed[10].clone()) },
+            Difference { index: 100, left: Some(flow[100].clone()), right: None },
+        ]);
+    }
+
+    const SAMPLE_CAPTURE: &str = "./data/sample.itch";
+
+    fn add(reference: u64, side: u8, shares: u32, stock: &str, price: u32, attribution: Option<&str>) -> Message {
+        Message::Add { reference, side, shares, stock: String::from(stock), price, attribution: attribution.map(String::from) }
+    }
+
+    // What data/sample.itch holds.
+    fn sample_messages() -> Vec<Message> {
+        let event = |code: u8| Message::Other(vec![b'S', 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, code]);
+        vec![
+            event(b'O'),
+            add(1, 0, 500, "QCUP", 479900, None),
+            add(2, 1, 300, "QCUP", 480500, None),
+            add(3, 0, 200, "QCUP", 479800, Some("GSCO")),
+            add(4, 0, 100, "OTHR", 100000, None),
+            Message::Executed { reference: 1, shares: 200, match_number: 1 },
+            Message::Cancel { reference: 2, shares: 100 },
+            Message::Replace { reference: 3, new_reference: 5, shares: 400, price: 480000 },
+            Message::Delete { reference: 4 },
+            add(6, 1, 1000, "QCUP", 480100, None),
+            Message::Executed { reference: 1, shares: 300, match_number: 2 },
+            Message::Delete { reference: 6 },
+            event(b'C'),
+        ]
+    }
+
+    #[test]
+    fn test_decode_sample_capture() {
+        let capture = fs::read(SAMPLE_CAPTURE).unwrap();
+        assert_eq!(decode(&capture).unwrap(), sample_messages());
+
+        let mut encoded = Vec::new();
+        for message in sample_messages() {
+            message.encode(&mut encoded);
+        }
+        assert_eq!(encoded, capture);
+    }
+
+    #[test]
+    fn test_capture_commands() {
+        let config = ItchConfig { stock: Some(String::from("QCUP")), ..ItchConfig::new() };
+        let commands = load_capture(SAMPLE_CAPTURE, &config).unwrap();
+        let qcup = |trader: &str, side: u8, price: u16, size: u64| Order {symbol: String::from("QCUP"), trader: String::from(trader), side, price, size};
+
+        assert_eq!(commands, vec![
+            Command::Limit(qcup("", 0, 4799, 500)),
+            Command::Limit(qcup("", 1, 4805, 300)),
+            Command::Limit(qcup("GSCO", 0, 4798, 200)),
+            Command::Amend { id: 1, size: 300 },
+            Command::Amend { id: 2, size: 200 },
+            Command::Replace { id: 3, order: qcup("GSCO", 0, 4800, 400) },
+            Command::Limit(qcup("", 1, 4801, 1000)),
+            Command::Cancel(1),
+            Command::Cancel(5),
+        ]);
+        playback(commands.as_slice());
+
+        // Unfiltered, the other stock's order takes an id too.
+        let commands = load_capture(SAMPLE_CAPTURE, &ItchConfig::new()).unwrap();
+        assert_eq!(commands[3], Command::Limit(Order {symbol: String::from("OTHR"), trader: String::new(), side: 0, price: 1000, size: 100}));
+        assert_eq!(commands[7], Command::Cancel(4));
+    }
+
+    #[test]
+    fn test_rejected_replace() {
+        let messages = vec![
+            add(1, 0, 10, "QCUP", 480000, None),
+            // Trades with order 1 in the engine, so the replace below finds nothing to cancel.
+            add(2, 1, 10, "QCUP", 480000, None),
+            Message::Replace { reference: 1, new_reference: 3, shares: 5, price: 479900 },
+            add(4, 0, 10, "QCUP", 479800, None),
+            Message::Delete { reference: 3 },
+            Message::Delete { reference: 4 },
+        ];
+        let commands = to_commands(&messages, &ItchConfig::new()).unwrap();
+        let qcup = |side: u8, price: u16, size: u64| Order {symbol: String::from("QCUP"), trader: String::new(), side, price, size};
+
+        // The rejected replace takes no id, so order 4 is the engine's third.
+        assert_eq!(commands, vec![
+            Command::Limit(qcup(0, 4800, 10)),
+            Command::Limit(qcup(1, 4800, 10)),
+            Command::Replace { id: 1, order: qcup(0, 4799, 5) },
+            Command::Limit(qcup(0, 4798, 10)),
+            Command::Cancel(3),
+        ]);
+    }
+
+    #[test]
+    fn test_partial_fill_on_entry() {
+        let messages = vec![
+            add(1, 1, 4, "QCUP", 480000, None),
+            // Crosses order 1 and rests the 6 shares left.
+            add(2, 0, 10, "QCUP", 480000, None),
+            Message::Cancel { reference: 2, shares: 2 },
+            Message::Cancel { reference: 2, shares: 4 },
+        ];
+        let commands = to_commands(&messages, &ItchConfig::new()).unwrap();
+        let qcup = |side: u8, size: u64| Order {symbol: String::from("QCUP"), trader: String::new(), side, price: 4800, size};
+
+        assert_eq!(commands, vec![
+            Command::Limit(qcup(1, 4)),
+            Command::Limit(qcup(0, 10)),
+            Command::Amend { id: 2, size: 4 },
+            Command::Cancel(2),
+        ]);
+
+        let mut engine = Engine::new();
+        for command in &commands[..3] {
+            engine.apply(command.clone());
+        }
+        assert_eq!(engine.resting(2).map(|x| x.size), Some(4));
+    }
+
+    #[test]
+    fn test_capture_errors() {
+        let capture = fs::read(SAMPLE_CAPTURE).unwrap();
+        assert!(matches!(decode(&capture[..capture.len() - 1]), Err(ItchError::Truncated { .. })));
+
+        let mut bad_side = Vec::new();
+        add(1, 0, 100, "QCUP", 479900, None).encode(&mut bad_side);
+        bad_side[2 + 19] = b'Z';
+        assert!(matches!(decode(&bad_side), Err(ItchError::Side { offset: 0, value: b'Z' })));
+
+        let mut short = Vec::new();
+        Message::Delete { reference: 1 }.encode(&mut short);
+        short.truncate(short.len() - 1);
+        short[1] -= 1;
+        assert!(matches!(decode(&short), Err(ItchError::Length { offset: 0, kind: 'D', length: 18 })));
+
+        // Half a cent cannot be priced in cents.
+        let messages = vec![add(1, 0, 100, "QCUP", 479950, None)];
+        assert!(matches!(to_commands(&messages, &ItchConfig::new()), Err(ItchError::Price { reference: 1, price: 479950 })));
+    }
+}
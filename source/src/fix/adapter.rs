@@ -0,0 +1,411 @@
+/*
+    Drives an engine from FIX 4.2 order messages and answers with execution reports.
+
+        NewOrderSingle (D)              Limit or Market, then New and a report per fill
+        OrderCancelRequest (F)          Cancel, then Canceled
+        OrderCancelReplaceRequest (G)   Replace, then Replaced and a report per fill
+    Orders the engine refuses are answered with a Rejected execution report (D) or an OrderCancelReject (F, G).
+
+    The engine runs price-time priority, so the resting side of the n-th trade of a message is the n-th
+    order it crosses in the opposite book. Those are noted before the message is applied and their
+    fills read from the execution log entries it adds.
+
+    Orders are identified by ClOrdID, each cancel or replace renames the order to its new ClOrdID.
+    OrderQty on a replace is the new total, the engine gets what remains after earlier fills.
+    The engine id is reported as OrderID, so it changes when an order is replaced.
+    The trader is Account if present, otherwise SenderCompID, to whom the reports are addressed.
+    Prices are decimals with config.decimals places, one engine tick being the last place.
+*/
+
+use std::collections::HashMap;
+use std::time::SystemTime;
+use crate::types::{Order, OrderId, Price, Side, Size};
+use crate::engine::engine::Engine;
+use crate::engine::session::{Phase, Reject};
+use crate::engine::command::{Applied, Command};
+use crate::fix::message::{msg_type, tag, utc_timestamp, FixError, Message};
+
+// ExecType and OrdStatus values.
+pub const NEW: char = '0';
+pub const PARTIALLY_FILLED: char = '1';
+pub const FILLED: char = '2';
+pub const CANCELED: char = '4';
+pub const REPLACED: char = '5';
+pub const REJECTED: char = '8';
+
+// OrdRejReason values.
+pub const BROKER_OPTION: char = '0';
+pub const EXCHANGE_CLOSED: char = '2';
+pub const DUPLICATE_ORDER: char = '6';
+
+fn ord_rej_reason(reject: &Reject) -> char {
+    match reject {
+        Reject::Phase(Phase::Closed) | Reject::Phase(Phase::Halted) => EXCHANGE_CLOSED,
+        _ => BROKER_OPTION,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FixConfig {
+    // SenderCompID of every report.
+    pub sender: String,
+    // Decimal places of FIX prices.
+    pub decimals: u32,
+}
+
+impl FixConfig {
+
+    // Prices in cents.
+    pub fn new(sender: &str) -> FixConfig {
+        FixConfig { sender: sender.to_string(), decimals: 2 }
+    }
+}
+
+pub fn parse_price(value: &str, decimals: u32) -> Result<Price, FixError> {
+    let invalid = || FixError::Invalid { tag: tag::PRICE, value: value.to_string() };
+    let (whole, fraction) = match value.find('.') {
+        Some(at) => (&value[..at], value[at + 1..].trim_end_matches('0')),
+        None => (value, ""),
+    };
+    if fraction.len() > decimals as usize || !fraction.bytes().all(|x| x.is_ascii_digit()) {
+        return Err(invalid());
+    }
+
+    let digits = format!("{}{:0<width$}", whole, fraction, width = decimals as usize);
+    match digits.parse::<Price>() {
+        Ok(ticks) if ticks > 0 => Ok(ticks),
+        _ => Err(invalid()),
+    }
+}
+
+pub fn format_price(ticks: u64, decimals: u32) -> String {
+    let scale = 10u64.pow(decimals);
+    if decimals == 0 {
+        return ticks.to_string();
+    }
+    format!("{}.{:0width$}", ticks / scale, ticks % scale, width = decimals as usize)
+}
+
+struct Tracked {
+    id: OrderId,
+    owner: String,
+    order: Order,
+    market: bool,
+    // OrderQty, fills included.
+    quantity: Size,
+    // Still on the book, as last seen.
+    leaves: Size,
+    cum: Size,
+    // Sum of price times quantity over fills, for AvgPx.
+    notional: u128,
+}
+
+impl Tracked {
+
+    fn status(&self) -> char {
+        if self.cum == 0 { NEW } else if self.leaves == 0 { FILLED } else { PARTIALLY_FILLED }
+    }
+}
+
+pub struct FixAdapter {
+    config: FixConfig,
+    engine: Engine,
+    // Open orders by ClOrdID, and their ClOrdID by engine id.
+    orders: HashMap<String, Tracked>,
+    ids: HashMap<OrderId, String>,
+    // Outbound MsgSeqNum per counterparty.
+    sequences: HashMap<String, u64>,
+    next_exec: u64,
+}
+
+fn side(message: &Message) -> Result<Side, FixError> {
+    match message.require(tag::SIDE)? {
+        "1" => Ok(0),
+        "2" => Ok(1),
+        value => Err(FixError::Invalid { tag: tag::SIDE, value: value.to_string() }),
+    }
+}
+
+fn quantity(message: &Message) -> Result<Size, FixError> {
+    let value = message.require(tag::ORDER_QTY)?;
+    match value.parse::<Size>() {
+        Ok(size) if size > 0 => Ok(size),
+        _ => Err(FixError::Invalid { tag: tag::ORDER_QTY, value: value.to_string() }),
+    }
+}
+
+impl FixAdapter {
+
+    pub fn new(config: FixConfig) -> FixAdapter {
+        // The execution log gives the incoming order's fills.
+        FixAdapter { config, engine: Engine::new_debug(), orders: HashMap::new(), ids: HashMap::new(), sequences: HashMap::new(), next_exec: 1 }
+    }
+
+    pub fn engine(&self) -> &Engine {
+        &self.engine
+    }
+
+    fn track(&mut self, cl_ord_id: String, tracked: Tracked) {
+        self.ids.insert(tracked.id, cl_ord_id.clone());
+        self.orders.insert(cl_ord_id, tracked);
+    }
+
+    fn untrack(&mut self, cl_ord_id: &str) -> Option<Tracked> {
+        let tracked = self.orders.remove(cl_ord_id)?;
+        self.ids.remove(&tracked.id);
+        Some(tracked)
+    }
+
+    fn header(&mut self, kind: &str, to: &str) -> Message {
+        let sequence = self.sequences.entry(to.to_string()).or_insert(0);
+        *sequence += 1;
+
+        let mut message = Message::new(kind);
+        message.push(tag::SENDER_COMP_ID, &self.config.sender)
+            .push(tag::TARGET_COMP_ID, to)
+            .push(tag::MSG_SEQ_NUM, *sequence)
+            .push(tag::SENDING_TIME, utc_timestamp(SystemTime::now()));
+        message
+    }
+
+    fn report(&mut self, cl_ord_id: &str, orig: Option<&str>, tracked: &Tracked, exec_type: char, last: Option<(Size, Price)>) -> Message {
+        let mut report = self.header(msg_type::EXECUTION_REPORT, &tracked.owner);
+        let decimals = self.config.decimals;
+
+        report.push(tag::ORDER_ID, tracked.id).push(tag::CL_ORD_ID, cl_ord_id);
+        if let Some(orig) = orig {
+            report.push(tag::ORIG_CL_ORD_ID, orig);
+        }
+        report.push(tag::EXEC_ID, self.next_exec)
+            .push(tag::EXEC_TRANS_TYPE, '0')
+            .push(tag::EXEC_TYPE, exec_type)
+            .push(tag::ORD_STATUS, if exec_type == CANCELED || exec_type == REJECTED { exec_type } else { tracked.status() })
+            .push(tag::SYMBOL, &tracked.order.symbol)
+            .push(tag::SIDE, if tracked.order.side == 0 { '1' } else { '2' })
+            .push(tag::ORDER_QTY, tracked.quantity)
+            .push(tag::ORD_TYPE, if tracked.market { '1' } else { '2' });
+        if !tracked.market {
+            report.push(tag::PRICE, format_price(tracked.order.price as u64, decimals));
+        }
+        if let Some((shares, price)) = last {
+            report.push(tag::LAST_SHARES, shares).push(tag::LAST_PX, format_price(price as u64, decimals));
+        }
+        let average = if tracked.cum == 0 { 0 } else { (tracked.notional / tracked.cum as u128) as u64 };
+        report.push(tag::LEAVES_QTY, tracked.leaves)
+            .push(tag::CUM_QTY, tracked.cum)
+            .push(tag::AVG_PX, format_price(average, decimals));
+
+        self.next_exec += 1;
+        report
+    }
+
+    // reason is the OrdRejReason of a rejected new order, cancel rejects carry their own CxlRejReason.
+    fn reject(&mut self, message: &Message, reason: char, text: &str) -> Message {
+        let owner = message.get(tag::SENDER_COMP_ID).unwrap_or("").to_string();
+
+        if message.msg_type() == msg_type::NEW_ORDER_SINGLE {
+            let mut report = self.header(msg_type::EXECUTION_REPORT, &owner);
+            report.push(tag::ORDER_ID, "NONE")
+                .push(tag::CL_ORD_ID, message.get(tag::CL_ORD_ID).unwrap_or(""))
+                .push(tag::EXEC_ID, self.next_exec)
+                .push(tag::EXEC_TRANS_TYPE, '0')
+                .push(tag::EXEC_TYPE, REJECTED)
+                .push(tag::ORD_STATUS, REJECTED)
+                .push(tag::SYMBOL, message.get(tag::SYMBOL).unwrap_or(""))
+                .push(tag::SIDE, message.get(tag::SIDE).unwrap_or(""))
+                .push(tag::LEAVES_QTY, 0)
+                .push(tag::CUM_QTY, 0)
+                .push(tag::AVG_PX, 0)
+                .push(tag::ORD_REJ_REASON, reason)
+                .push(tag::TEXT, text);
+            self.next_exec += 1;
+            return report;
+        }
+
+        let orig = message.get(tag::ORIG_CL_ORD_ID).unwrap_or("");
+        let (order_id, status) = match self.orders.get(orig) {
+            Some(tracked) => (tracked.id.to_string(), tracked.status()),
+            None => (String::from("NONE"), REJECTED),
+        };
+        let mut reject = self.header(msg_type::ORDER_CANCEL_REJECT, &owner);
+        reject.push(tag::ORDER_ID, order_id)
+            .push(tag::CL_ORD_ID, message.get(tag::CL_ORD_ID).unwrap_or(""))
+            .push(tag::ORIG_CL_ORD_ID, orig)
+            .push(tag::ORD_STATUS, status)
+            .push(tag::CXL_REJ_RESPONSE_TO, if message.msg_type() == msg_type::ORDER_CANCEL_REQUEST { '1' } else { '2' })
+            .push(tag::CXL_REJ_REASON, if status == REJECTED { '1' } else { '0' })
+            .push(tag::TEXT, text);
+        reject
+    }
+
+    fn order(&self, message: &Message, side: Side, quantity: Size) -> Result<(Order, bool), FixError> {
+        let market = match message.get(tag::ORD_TYPE).unwrap_or("2") {
+            "1" => true,
+            "2" => false,
+            value => return Err(FixError::Invalid { tag: tag::ORD_TYPE, value: value.to_string() }),
+        };
+        let price = if market { 0 } else { parse_price(message.require(tag::PRICE)?, self.config.decimals)? };
+
+        let trader = message.get(tag::ACCOUNT).or_else(|| message.get(tag::SENDER_COMP_ID)).unwrap_or("");
+        Ok((Order { symbol: message.require(tag::SYMBOL)?.to_string(), trader: trader.to_string(), side, price, size: quantity }, market))
+    }
+
+    // The engine command for an order message. Cancels and replaces must name an open order.
+    pub fn command(&self, message: &Message) -> Result<Command, FixError> {
+        match message.msg_type() {
+            msg_type::NEW_ORDER_SINGLE => {
+                let (order, market) = self.order(message, side(message)?, quantity(message)?)?;
+                Ok(if market { Command::Market(order) } else { Command::Limit(order) })
+            }
+            msg_type::ORDER_CANCEL_REQUEST | msg_type::ORDER_CANCEL_REPLACE_REQUEST => {
+                let orig = message.require(tag::ORIG_CL_ORD_ID)?;
+                let tracked = self.orders.get(orig).ok_or_else(|| FixError::Invalid { tag: tag::ORIG_CL_ORD_ID, value: orig.to_string() })?;
+                if message.msg_type() == msg_type::ORDER_CANCEL_REQUEST {
+                    return Ok(Command::Cancel(tracked.id));
+                }
+
+                let total = quantity(message)?;
+                if total <= tracked.cum {
+                    return Err(FixError::Invalid { tag: tag::ORDER_QTY, value: total.to_string() });
+                }
+                let (order, market) = self.order(message, tracked.order.side, total - tracked.cum)?;
+                if market {
+                    return Err(FixError::Invalid { tag: tag::ORD_TYPE, value: String::from("1") });
+                }
+                Ok(Command::Replace { id: tracked.id, order })
+            }
+            other => Err(FixError::Unsupported(other.to_string())),
+        }
+    }
+
+    // The resting orders an order would trade with, in the order the engine takes them.
+    fn counterparties(&self, order: &Order, market: bool) -> Vec<OrderId> {
+        let mut remaining = order.size;
+        let mut ids = Vec::new();
+        for resting in self.engine.book(order.side ^ 1) {
+            let crosses = market || if order.side == 0 { order.price >= resting.order.price } else { order.price <= resting.order.price };
+            if remaining == 0 || !crosses {
+                break;
+            }
+            remaining -= remaining.min(resting.order.size);
+            ids.push(resting.id);
+        }
+        ids
+    }
+
+    // Reports for the incoming order's fills and for every resting order it traded against.
+    fn fills(&mut self, cl_ord_id: &str, counterparties: &[OrderId], reports: &mut Vec<Message>) {
+        let log = std::mem::take(&mut self.engine.execution_log);
+        let mut incoming = match self.untrack(cl_ord_id) {
+            Some(tracked) => tracked,
+            None => return,
+        };
+
+        // The log holds the incoming order's side of each trade, then the resting side.
+        for execution in log.iter().step_by(2) {
+            incoming.cum += execution.size;
+            incoming.leaves -= execution.size;
+            incoming.notional += execution.price as u128 * execution.size as u128;
+            let exec_type = if incoming.leaves == 0 { FILLED } else { PARTIALLY_FILLED };
+            reports.push(self.report(cl_ord_id, None, &incoming, exec_type, Some((execution.size, execution.price))));
+        }
+
+        for (execution, id) in log.iter().skip(1).step_by(2).zip(counterparties) {
+            let resting_id = match self.ids.get(id) {
+                Some(resting_id) => resting_id.clone(),
+                None => continue,
+            };
+            let mut resting = self.untrack(&resting_id).unwrap();
+            resting.cum += execution.size;
+            resting.leaves -= execution.size;
+            resting.notional += execution.price as u128 * execution.size as u128;
+            let exec_type = if resting.leaves == 0 { FILLED } else { PARTIALLY_FILLED };
+            reports.push(self.report(&resting_id, None, &resting, exec_type, Some((execution.size, execution.price))));
+            if resting.leaves > 0 {
+                self.track(resting_id, resting);
+            }
+        }
+
+        // Whatever a market order did not fill is dropped by the engine.
+        if incoming.market && incoming.leaves > 0 {
+            incoming.leaves = 0;
+            reports.push(self.report(cl_ord_id, None, &incoming, CANCELED, None));
+        } else if incoming.leaves > 0 {
+            self.track(cl_ord_id.to_string(), incoming);
+        }
+    }
+
+    // Applies one inbound message and returns the reports to send, in order.
+    // Errors are left for the session layer: an unsupported message type, or one without a ClOrdID.
+    pub fn handle(&mut self, message: &Message) -> Result<Vec<Message>, FixError> {
+        let kind = message.msg_type();
+        if kind != msg_type::NEW_ORDER_SINGLE && kind != msg_type::ORDER_CANCEL_REQUEST && kind != msg_type::ORDER_CANCEL_REPLACE_REQUEST {
+            return Err(FixError::Unsupported(kind.to_string()));
+        }
+        let cl_ord_id = message.require(tag::CL_ORD_ID)?.to_string();
+        if self.orders.contains_key(&cl_ord_id) {
+            return Ok(vec![self.reject(message, DUPLICATE_ORDER, "duplicate ClOrdID")]);
+        }
+
+        let command = match self.command(message) {
+            Ok(command) => command,
+            Err(e) => return Ok(vec![self.reject(message, BROKER_OPTION, &e.to_string())]),
+        };
+        let orig = message.get(tag::ORIG_CL_ORD_ID).map(String::from);
+        let counterparties = match &command {
+            Command::Limit(order) | Command::Replace { order, .. } => self.counterparties(order, false),
+            Command::Market(order) => self.counterparties(order, true),
+            _ => Vec::new(),
+        };
+        self.engine.execution_log.clear();
+
+        let mut reports = Vec::new();
+        match (self.engine.apply(command.clone()), command) {
+            (Applied::Order(Ok(id)), Command::Limit(order)) | (Applied::Order(Ok(id)), Command::Market(order)) => {
+                let tracked = Tracked {
+                    id,
+                    owner: message.get(tag::SENDER_COMP_ID).unwrap_or("").to_string(),
+                    market: matches!(message.get(tag::ORD_TYPE), Some("1")),
+                    quantity: order.size,
+                    leaves: order.size,
+                    cum: 0,
+                    notional: 0,
+                    order,
+                };
+                reports.push(self.report(&cl_ord_id, None, &tracked, NEW, None));
+                self.track(cl_ord_id.clone(), tracked);
+                self.fills(&cl_ord_id, &counterparties, &mut reports);
+            }
+            (Applied::Order(Ok(id)), Command::Replace { order, .. }) => {
+                let orig = orig.unwrap_or_default();
+                let mut tracked = self.untrack(&orig).unwrap();
+                tracked.id = id;
+                tracked.quantity = tracked.cum + order.size;
+                tracked.leaves = order.size;
+                tracked.order = order;
+                reports.push(self.report(&cl_ord_id, Some(&orig), &tracked, REPLACED, None));
+                self.track(cl_ord_id.clone(), tracked);
+                self.fills(&cl_ord_id, &counterparties, &mut reports);
+            }
+            (Applied::Cancel(Ok(())), _) => {
+                let orig = orig.unwrap_or_default();
+                let mut tracked = self.untrack(&orig).unwrap();
+                tracked.leaves = 0;
+                reports.push(self.report(&cl_ord_id, Some(&orig), &tracked, CANCELED, None));
+            }
+            (Applied::Order(Err(reject)), _) | (Applied::Cancel(Err(reject)), _) => {
+                let reason = ord_rej_reason(&reject);
+                reports.push(self.reject(message, reason, &reject.to_string()));
+            }
+            (applied, _) => unreachable!("{:?} for an order message", applied),
+        }
+
+        Ok(reports)
+    }
+
+    // handle for raw bytes, with the reports encoded.
+    pub fn handle_bytes(&mut self, bytes: &[u8]) -> Result<Vec<Vec<u8>>, FixError> {
+        let reports = self.handle(&Message::parse(bytes)?)?;
+        Ok(reports.iter().map(|x| x.encode()).collect())
+    }
+}
@@ -0,0 +1,203 @@
+/*
+    FIX 4.2 tag=value messages.
+
+    On the wire a message is
+        8=FIX.4.2 SOH 9=<body length> SOH 35=<type> SOH ... SOH 10=<checksum> SOH
+    where the body length counts the bytes after the 9 field up to and including the SOH before 10,
+    and the checksum is the sum of every byte before 10 modulo 256, written as three digits.
+*/
+
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const SOH: u8 = 0x01;
+pub const BEGIN_STRING: &str = "FIX.4.2";
+
+pub mod tag {
+    pub const ACCOUNT: u32 = 1;
+    pub const AVG_PX: u32 = 6;
+    pub const BEGIN_STRING: u32 = 8;
+    pub const BODY_LENGTH: u32 = 9;
+    pub const CHECKSUM: u32 = 10;
+    pub const CL_ORD_ID: u32 = 11;
+    pub const CUM_QTY: u32 = 14;
+    pub const EXEC_ID: u32 = 17;
+    pub const EXEC_TRANS_TYPE: u32 = 20;
+    pub const LAST_PX: u32 = 31;
+    pub const LAST_SHARES: u32 = 32;
+    pub const MSG_SEQ_NUM: u32 = 34;
+    pub const MSG_TYPE: u32 = 35;
+    pub const ORDER_ID: u32 = 37;
+    pub const ORDER_QTY: u32 = 38;
+    pub const ORD_STATUS: u32 = 39;
+    pub const ORD_TYPE: u32 = 40;
+    pub const ORIG_CL_ORD_ID: u32 = 41;
+    pub const PRICE: u32 = 44;
+    pub const SENDER_COMP_ID: u32 = 49;
+    pub const SENDING_TIME: u32 = 52;
+    pub const SIDE: u32 = 54;
+    pub const SYMBOL: u32 = 55;
+    pub const TARGET_COMP_ID: u32 = 56;
+    pub const TEXT: u32 = 58;
+    pub const CXL_REJ_REASON: u32 = 102;
+    pub const ORD_REJ_REASON: u32 = 103;
+    pub const EXEC_TYPE: u32 = 150;
+    pub const LEAVES_QTY: u32 = 151;
+    pub const CXL_REJ_RESPONSE_TO: u32 = 434;
+}
+
+pub mod msg_type {
+    pub const EXECUTION_REPORT: &str = "8";
+    pub const ORDER_CANCEL_REJECT: &str = "9";
+    pub const NEW_ORDER_SINGLE: &str = "D";
+    pub const ORDER_CANCEL_REQUEST: &str = "F";
+    pub const ORDER_CANCEL_REPLACE_REQUEST: &str = "G";
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FixError {
+    // Not a tag=value message, or the standard header and trailer are out of place.
+    Malformed(String),
+    BodyLength { declared: usize, actual: usize },
+    Checksum { declared: u8, actual: u8 },
+    // A required field is absent.
+    Missing(u32),
+    Invalid { tag: u32, value: String },
+    // A message type the adapter does not handle.
+    Unsupported(String),
+}
+
+impl fmt::Display for FixError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FixError::Malformed(reason) => write!(f, "malformed message: {}", reason),
+            FixError::BodyLength { declared, actual } => write!(f, "body length {} declared, {} found", declared, actual),
+            FixError::Checksum { declared, actual } => write!(f, "checksum {:03} declared, {:03} computed", declared, actual),
+            FixError::Missing(tag) => write!(f, "required tag {} missing", tag),
+            FixError::Invalid { tag, value } => write!(f, "tag {}: invalid value '{}'", tag, value),
+            FixError::Unsupported(kind) => write!(f, "unsupported message type '{}'", kind),
+        }
+    }
+}
+
+impl std::error::Error for FixError {}
+
+pub fn checksum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |sum, x| sum.wrapping_add(*x))
+}
+
+// Body fields in order, starting with MsgType. BeginString, BodyLength and CheckSum are added by encode.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Message {
+    pub fields: Vec<(u32, String)>,
+}
+
+impl Message {
+
+    pub fn new(msg_type: &str) -> Message {
+        Message { fields: vec![(tag::MSG_TYPE, msg_type.to_string())] }
+    }
+
+    pub fn msg_type(&self) -> &str {
+        self.get(tag::MSG_TYPE).unwrap_or("")
+    }
+
+    // The first occurrence of tag.
+    pub fn get(&self, tag: u32) -> Option<&str> {
+        self.fields.iter().find(|x| x.0 == tag).map(|x| x.1.as_str())
+    }
+
+    pub fn require(&self, tag: u32) -> Result<&str, FixError> {
+        self.get(tag).ok_or(FixError::Missing(tag))
+    }
+
+    pub fn push<T: ToString>(&mut self, tag: u32, value: T) -> &mut Message {
+        self.fields.push((tag, value.to_string()));
+        self
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (tag, value) in &self.fields {
+            body.extend(format!("{}={}", tag, value).as_bytes());
+            body.push(SOH);
+        }
+
+        let mut out = format!("8={}\x019={}\x01", BEGIN_STRING, body.len()).into_bytes();
+        out.extend(body);
+        let sum = checksum(&out);
+        out.extend(format!("10={:03}\x01", sum).as_bytes());
+        out
+    }
+
+    // Checks the header and trailer of one complete message.
+    pub fn parse(bytes: &[u8]) -> Result<Message, FixError> {
+        if bytes.last() != Some(&SOH) {
+            return Err(FixError::Malformed(String::from("does not end with SOH")));
+        }
+
+        let mut fields = Vec::new();
+        // Byte offset at which each field starts, needed for the length and checksum.
+        let mut starts = Vec::new();
+        let mut start = 0;
+        for field in bytes[..bytes.len() - 1].split(|x| *x == SOH) {
+            let text = std::str::from_utf8(field).map_err(|_| FixError::Malformed(String::from("not ascii")))?;
+            let (tag, value) = match text.find('=') {
+                Some(at) => (&text[..at], &text[at + 1..]),
+                None => return Err(FixError::Malformed(format!("field '{}' has no '='", text))),
+            };
+            let tag: u32 = tag.parse().map_err(|_| FixError::Malformed(format!("tag '{}' is not a number", tag)))?;
+            fields.push((tag, value.to_string()));
+            starts.push(start);
+            start += field.len() + 1;
+        }
+
+        let count = fields.len();
+        if count < 4 || fields[0].0 != tag::BEGIN_STRING || fields[1].0 != tag::BODY_LENGTH
+            || fields[2].0 != tag::MSG_TYPE || fields[count - 1].0 != tag::CHECKSUM {
+            return Err(FixError::Malformed(String::from("expected 8, 9 and 35 first and 10 last")));
+        }
+        if fields[0].1 != BEGIN_STRING {
+            return Err(FixError::Invalid { tag: tag::BEGIN_STRING, value: fields[0].1.clone() });
+        }
+
+        let declared = fields[1].1.parse().map_err(|_| FixError::Invalid { tag: tag::BODY_LENGTH, value: fields[1].1.clone() })?;
+        let actual = starts[count - 1] - starts[2];
+        if declared != actual {
+            return Err(FixError::BodyLength { declared, actual });
+        }
+
+        let trailer = &fields[count - 1].1;
+        let declared = match trailer.parse::<u8>() {
+            Ok(sum) if trailer.len() == 3 => sum,
+            _ => return Err(FixError::Invalid { tag: tag::CHECKSUM, value: trailer.clone() }),
+        };
+        let actual = checksum(&bytes[..starts[count - 1]]);
+        if declared != actual {
+            return Err(FixError::Checksum { declared, actual });
+        }
+
+        Ok(Message { fields: fields[2..count - 1].to_vec() })
+    }
+}
+
+// SendingTime format, YYYYMMDD-HH:MM:SS.sss in UTC.
+pub fn utc_timestamp(time: SystemTime) -> String {
+    let since = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since.as_secs();
+    let (days, second) = ((seconds / 86_400) as i64, seconds % 86_400);
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}{:02}{:02}-{:02}:{:02}:{:02}.{:03}", year, month, day,
+        second / 3600, second % 3600 / 60, second % 60, since.subsec_millis())
+}
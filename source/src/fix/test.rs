@@ -0,0 +1,166 @@
+#[cfg(test)]
+mod fix_tests {
+
+    use std::time::{Duration, UNIX_EPOCH};
+    use crate::engine::command::Command;
+    use crate::fix::message::{checksum, msg_type, tag, utc_timestamp, FixError, Message, SOH};
+    use crate::fix::adapter::{format_price, parse_price, FixAdapter, FixConfig};
+
+    fn wire(text: &str) -> Vec<u8> {
+        text.replace('|', "\x01").into_bytes()
+    }
+
+    fn order_message(kind: &str, sender: &str, fields: &[(u32, &str)]) -> Message {
+        let mut message = Message::new(kind);
+        message.push(tag::SENDER_COMP_ID, sender).push(tag::TARGET_COMP_ID, "ENGINE");
+        for (tag, value) in fields {
+            message.push(*tag, value);
+        }
+        message
+    }
+
+    fn new_order(sender: &str, cl_ord_id: &str, side: &str, quantity: &str, price: Option<&str>) -> Message {
+        let mut fields = vec![(tag::CL_ORD_ID, cl_ord_id), (tag::SYMBOL, "SYM"), (tag::SIDE, side), (tag::ORDER_QTY, quantity)];
+        match price {
+            Some(price) => fields.extend(&[(tag::ORD_TYPE, "2"), (tag::PRICE, price)]),
+            None => fields.push((tag::ORD_TYPE, "1")),
+        }
+        order_message(msg_type::NEW_ORDER_SINGLE, sender, &fields)
+    }
+
+    // (TargetCompID, ClOrdID, ExecType or "9" for a cancel reject, LeavesQty, CumQty, LastShares)
+    fn summary(report: &Message) -> (&str, &str, &str, &str, &str, &str) {
+        let get = |tag| report.get(tag).unwrap_or("-");
+        let exec_type = if report.msg_type() == msg_type::ORDER_CANCEL_REJECT { "9" } else { get(tag::EXEC_TYPE) };
+        (get(tag::TARGET_COMP_ID), get(tag::CL_ORD_ID), exec_type, get(tag::LEAVES_QTY), get(tag::CUM_QTY), get(tag::LAST_SHARES))
+    }
+
+    #[test]
+    fn test_message_framing() {
+        let bytes = wire("8=FIX.4.2|9=39|35=D|49=OMS|56=ENGINE|11=1|55=SYM|54=1|10=047|");
+        assert_eq!(checksum(&bytes[..bytes.len() - 7]), 47);
+
+        let message = Message::parse(&bytes).unwrap();
+        assert_eq!(message.msg_type(), msg_type::NEW_ORDER_SINGLE);
+        assert_eq!(message.get(tag::SYMBOL), Some("SYM"));
+        assert_eq!(message.encode(), bytes);
+
+        assert_eq!(Message::parse(&wire("8=FIX.4.2|9=40|35=D|49=OMS|56=ENGINE|11=1|55=SYM|54=1|10=047|")),
+            Err(FixError::BodyLength { declared: 40, actual: 39 }));
+        assert_eq!(Message::parse(&wire("8=FIX.4.2|9=39|35=D|49=OMS|56=ENGINE|11=2|55=SYM|54=1|10=047|")),
+            Err(FixError::Checksum { declared: 47, actual: 48 }));
+        assert!(matches!(Message::parse(&wire("9=40|8=FIX.4.2|35=D|10=198|")), Err(FixError::Malformed(_))));
+        assert!(matches!(Message::parse(&wire("8=FIX.4.2|9=5|35=D|10=198")), Err(FixError::Malformed(_))));
+        assert!(matches!(Message::parse(&wire("8=FIX.4.4|9=5|35=D|10=198|")), Err(FixError::Invalid { tag: 8, .. })));
+    }
+
+    #[test]
+    fn test_prices_and_times() {
+        assert_eq!(parse_price("47.99", 2), Ok(4799));
+        assert_eq!(parse_price("48", 2), Ok(4800));
+        assert_eq!(parse_price("48.100", 2), Ok(4810));
+        assert!(parse_price("47.995", 2).is_err());
+        assert!(parse_price("-1", 2).is_err());
+        assert!(parse_price("0", 2).is_err());
+        assert_eq!(format_price(4799, 2), "47.99");
+        assert_eq!(format_price(5, 2), "0.05");
+
+        assert_eq!(utc_timestamp(UNIX_EPOCH), "19700101-00:00:00.000");
+        assert_eq!(utc_timestamp(UNIX_EPOCH + Duration::from_millis(1_709_251_199_250)), "20240229-23:59:59.250");
+    }
+
+    #[test]
+    fn test_order_lifecycle() {
+        let mut adapter = FixAdapter::new(FixConfig::new("ENGINE"));
+
+        let reports = adapter.handle(&new_order("SELLER", "S1", "2", "100", Some("48.00"))).unwrap();
+        assert_eq!(reports.iter().map(summary).collect::<Vec<_>>(), vec![("SELLER", "S1", "0", "100", "0", "-")]);
+
+        // Crosses the resting sell, both sides get a report.
+        let reports = adapter.handle(&new_order("BUYER", "B1", "1", "150", Some("48.00"))).unwrap();
+        assert_eq!(reports.iter().map(summary).collect::<Vec<_>>(), vec![
+            ("BUYER", "B1", "0", "150", "0", "-"),
+            ("BUYER", "B1", "1", "50", "100", "100"),
+            ("SELLER", "S1", "2", "0", "100", "100"),
+        ]);
+        assert_eq!(reports[2].get(tag::LAST_PX), Some("48.00"));
+        assert_eq!(reports[2].get(tag::ORD_STATUS), Some("2"));
+
+        // OrderQty is the new total, 100 already filled leaves 100.
+        let replace = order_message(msg_type::ORDER_CANCEL_REPLACE_REQUEST, "BUYER", &[(tag::CL_ORD_ID, "B2"), (tag::ORIG_CL_ORD_ID, "B1"),
+            (tag::SYMBOL, "SYM"), (tag::SIDE, "1"), (tag::ORDER_QTY, "200"), (tag::ORD_TYPE, "2"), (tag::PRICE, "47.99")]);
+        let reports = adapter.handle(&replace).unwrap();
+        assert_eq!(reports.iter().map(summary).collect::<Vec<_>>(), vec![("BUYER", "B2", "5", "100", "100", "-")]);
+        assert_eq!(reports[0].get(tag::ORIG_CL_ORD_ID), Some("B1"));
+        assert_eq!(reports[0].get(tag::ORD_STATUS), Some("1"));
+        assert_eq!(adapter.engine().resting(3).map(|x| (x.price, x.size)), Some((4799, 100)));
+
+        // B1 is gone once replaced.
+        let cancel = |cl_ord_id, orig| order_message(msg_type::ORDER_CANCEL_REQUEST, "BUYER",
+            &[(tag::CL_ORD_ID, cl_ord_id), (tag::ORIG_CL_ORD_ID, orig), (tag::SYMBOL, "SYM"), (tag::SIDE, "1")]);
+        let reports = adapter.handle(&cancel("B3", "B1")).unwrap();
+        assert_eq!(reports.iter().map(summary).collect::<Vec<_>>(), vec![("BUYER", "B3", "9", "-", "-", "-")]);
+        assert_eq!(reports[0].get(tag::CXL_REJ_RESPONSE_TO), Some("1"));
+
+        let reports = adapter.handle(&cancel("B4", "B2")).unwrap();
+        assert_eq!(reports.iter().map(summary).collect::<Vec<_>>(), vec![("BUYER", "B4", "4", "0", "100", "-")]);
+        assert!(adapter.engine().resting(3).is_none());
+
+        // Sequence numbers run per counterparty.
+        assert_eq!(reports[0].get(tag::MSG_SEQ_NUM), Some("5"));
+    }
+
+    #[test]
+    fn test_market_orders_and_rejects() {
+        let mut adapter = FixAdapter::new(FixConfig::new("ENGINE"));
+        adapter.handle(&new_order("SELLER", "S1", "2", "100", Some("48.00"))).unwrap();
+        adapter.handle(&new_order("SELLER", "S2", "2", "100", Some("48.01"))).unwrap();
+
+        // Sweeps both levels at the resting prices and drops the rest.
+        let reports = adapter.handle(&new_order("BUYER", "M1", "1", "250", None)).unwrap();
+        assert_eq!(reports.iter().map(summary).collect::<Vec<_>>(), vec![
+            ("BUYER", "M1", "0", "250", "0", "-"),
+            ("BUYER", "M1", "1", "150", "100", "100"),
+            ("BUYER", "M1", "1", "50", "200", "100"),
+            ("SELLER", "S1", "2", "0", "100", "100"),
+            ("SELLER", "S2", "2", "0", "100", "100"),
+            ("BUYER", "M1", "4", "0", "200", "-"),
+        ]);
+        assert_eq!(reports[4].get(tag::LAST_PX), Some("48.01"));
+        assert_eq!(reports[5].get(tag::AVG_PX), Some("48.00"));
+
+        let reports = adapter.handle(&new_order("BUYER", "B1", "1", "100", Some("48.005"))).unwrap();
+        assert_eq!(reports.iter().map(summary).collect::<Vec<_>>(), vec![("BUYER", "B1", "8", "0", "0", "-")]);
+        assert!(reports[0].get(tag::TEXT).unwrap().contains("tag 44"));
+        assert_eq!(reports[0].get(tag::ORD_REJ_REASON), Some("0"));
+
+        adapter.handle(&new_order("BUYER", "B2", "1", "100", Some("47.00"))).unwrap();
+        let reports = adapter.handle(&new_order("BUYER", "B2", "1", "100", Some("47.00"))).unwrap();
+        assert_eq!(reports[0].get(tag::TEXT), Some("duplicate ClOrdID"));
+        assert_eq!(reports[0].get(tag::ORD_REJ_REASON), Some("6"));
+
+        assert_eq!(adapter.handle(&Message::new("0")), Err(FixError::Unsupported(String::from("0"))));
+        assert_eq!(adapter.handle(&order_message(msg_type::NEW_ORDER_SINGLE, "BUYER", &[])), Err(FixError::Missing(tag::CL_ORD_ID)));
+    }
+
+    #[test]
+    fn test_commands_and_wire() {
+        let mut adapter = FixAdapter::new(FixConfig::new("ENGINE"));
+        let order = new_order("OMS", "1", "1", "500", Some("47.99"));
+        match adapter.command(&order).unwrap() {
+            Command::Limit(order) => assert_eq!((order.trader.as_str(), order.side, order.price, order.size), ("OMS", 0, 4799, 500)),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let mut with_account = order.clone();
+        with_account.push(tag::ACCOUNT, "ID8");
+        assert!(matches!(adapter.command(&with_account), Ok(Command::Limit(order)) if order.trader == "ID8"));
+
+        let reports = adapter.handle_bytes(&order.encode()).unwrap();
+        assert_eq!(reports.len(), 1);
+        let report = Message::parse(&reports[0]).unwrap();
+        assert_eq!(report.msg_type(), msg_type::EXECUTION_REPORT);
+        assert_eq!(report.get(tag::PRICE), Some("47.99"));
+        assert_eq!(reports[0].iter().filter(|x| **x == SOH).count(), report.fields.len() + 3);
+    }
+}
@@ -0,0 +1,187 @@
+/*
+    Append-only log of inbound commands, written before the command reaches the engine.
+
+    Each record is laid out as
+        length: u32 LE | crc32 of payload: u32 LE | payload: JSON JournalRecord
+*/
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::convert::TryInto;
+use std::io::{self, Read, Write, Seek, SeekFrom};
+use std::path::Path;
+use serde::{Serialize, Deserialize};
+use crate::engine::command::Command;
+use crate::engine::snapshot::SnapshotError;
+
+const HEADER_LEN: usize = 8;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalRecord {
+    // 1 for the first command ever journaled, never reused.
+    pub sequence: u64,
+    pub command: Command,
+}
+
+#[derive(Debug)]
+pub enum JournalError {
+    Io(io::Error),
+    Snapshot(SnapshotError),
+    // A damaged record with intact records after it, this is not a torn write.
+    Corrupt { offset: u64 },
+}
+
+impl fmt::Display for JournalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JournalError::Io(e) => write!(f, "journal io error: {}", e),
+            JournalError::Snapshot(e) => write!(f, "{}", e),
+            JournalError::Corrupt { offset } => write!(f, "journal corrupt at byte {}", offset),
+        }
+    }
+}
+
+impl std::error::Error for JournalError {}
+
+impl From<io::Error> for JournalError {
+    fn from(e: io::Error) -> Self {
+        JournalError::Io(e)
+    }
+}
+
+impl From<SnapshotError> for JournalError {
+    fn from(e: SnapshotError) -> Self {
+        JournalError::Snapshot(e)
+    }
+}
+
+// CRC-32 (IEEE), bitwise since the journal is bound by the disk rather than the checksum.
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+pub struct Journal {
+    file: File,
+    // fsync every record, turn off for simulations that can afford to lose the tail.
+    sync: bool,
+}
+
+impl Journal {
+
+    // Opens (or creates) the journal and returns every intact record in it.
+    // A torn final record is dropped and the file truncated so appends continue after the last good one.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<(Journal, Vec<JournalRecord>), JournalError> {
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+
+        let (records, valid) = Journal::parse(&bytes)?;
+        file.set_len(valid as u64)?;
+        file.seek(SeekFrom::End(0))?;
+
+        Ok((Journal { file, sync: true }, records))
+    }
+
+    // Reads the intact records of a journal without opening it for writing.
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Vec<JournalRecord>, JournalError> {
+        let bytes = std::fs::read(path)?;
+        let (records, _valid) = Journal::parse(&bytes)?;
+        Ok(records)
+    }
+
+    pub fn set_sync(&mut self, sync: bool) {
+        self.sync = sync;
+    }
+
+    pub fn append(&mut self, record: &JournalRecord) -> Result<(), JournalError> {
+        let payload = serde_json::to_vec(record).map_err(io::Error::from)?;
+
+        let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+        bytes.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&crc32(&payload).to_le_bytes());
+        bytes.extend_from_slice(&payload);
+
+        let start = self.file.stream_position()?;
+        let written = self.file.write_all(&bytes).and_then(|_| if self.sync { self.file.sync_data() } else { Ok(()) });
+        if let Err(e) = written {
+            // A partial record left behind would sit between intact ones and make the journal corrupt.
+            self.file.set_len(start)?;
+            self.file.seek(SeekFrom::Start(start))?;
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    // Drops every record, used once a snapshot covers them.
+    pub fn truncate(&mut self) -> Result<(), JournalError> {
+        self.file.set_len(0)?;
+        self.file.seek(SeekFrom::Start(0))?;
+        if self.sync {
+            self.file.sync_data()?;
+        }
+        Ok(())
+    }
+
+    // Whether an intact record starts anywhere from offset on.
+    fn intact_after(bytes: &[u8], from: usize) -> bool {
+        (from..bytes.len().saturating_sub(HEADER_LEN)).any(|offset| {
+            let len = u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()) as usize;
+            let crc = u32::from_le_bytes(bytes[offset + 4..offset + 8].try_into().unwrap());
+            let end = offset + HEADER_LEN + len;
+            end <= bytes.len() && crc32(&bytes[offset + HEADER_LEN..end]) == crc
+                && serde_json::from_slice::<JournalRecord>(&bytes[offset + HEADER_LEN..end]).is_ok()
+        })
+    }
+
+    // Returns the intact records and the length of the prefix holding them.
+    fn parse(bytes: &[u8]) -> Result<(Vec<JournalRecord>, usize), JournalError> {
+        let mut records = Vec::new();
+        let mut offset = 0;
+
+        while offset < bytes.len() {
+            let torn = Err(offset);
+            let record = if bytes.len() - offset < HEADER_LEN {
+                torn
+            } else {
+                let len = u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()) as usize;
+                let crc = u32::from_le_bytes(bytes[offset + 4..offset + 8].try_into().unwrap());
+                let end = offset + HEADER_LEN + len;
+
+                if end > bytes.len() {
+                    // A damaged length can also overrun the file, that is only torn if nothing intact follows.
+                    if Journal::intact_after(bytes, offset + 1) {
+                        return Err(JournalError::Corrupt { offset: offset as u64 });
+                    }
+                    torn
+                } else {
+                    let payload = &bytes[offset + HEADER_LEN..end];
+                    match serde_json::from_slice::<JournalRecord>(payload) {
+                        Ok(record) if crc32(payload) == crc => Ok((record, end)),
+                        _ if end == bytes.len() => torn,
+                        _ => return Err(JournalError::Corrupt { offset: offset as u64 }),
+                    }
+                }
+            };
+
+            match record {
+                Ok((record, end)) => {
+                    records.push(record);
+                    offset = end;
+                }
+                // Only the final record can be torn, everything before it was synced.
+                Err(valid) => return Ok((records, valid)),
+            }
+        }
+
+        Ok((records, offset))
+    }
+}
@@ -0,0 +1,3 @@
+pub mod journal;
+pub mod recovery;
+mod test;
@@ -0,0 +1,104 @@
+/*
+    Engine run as a long-lived service: commands are journaled before they are applied and the
+    book is rebuilt on restart from the latest snapshot plus the journal written since.
+
+    A directory holds
+        journal.log                    commands since the last checkpoint
+        snapshot-<sequence>.json       engine state after <sequence> commands
+*/
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::engine::engine::Engine;
+use crate::engine::matching::MatchingPolicy;
+use crate::engine::command::{Command, Applied};
+use crate::engine::snapshot::Snapshot;
+use crate::journal::journal::{Journal, JournalRecord, JournalError};
+
+const JOURNAL_FILE: &str = "journal.log";
+
+pub struct Journaled {
+    engine: Engine,
+    journal: Journal,
+    dir: PathBuf,
+    // Sequence of the last command applied.
+    sequence: u64,
+}
+
+fn snapshot_sequence(path: &Path) -> Option<u64> {
+    let name = path.file_name()?.to_str()?;
+    name.strip_prefix("snapshot-")?.strip_suffix(".json")?.parse().ok()
+}
+
+// Snapshots in dir, oldest first.
+fn snapshots(dir: &Path) -> Result<Vec<(u64, PathBuf)>, JournalError> {
+    let mut found = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if let Some(sequence) = snapshot_sequence(&path) {
+            found.push((sequence, path));
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+impl Journaled {
+
+    // Recovers the engine kept in dir, or starts an empty one. policy must be the one used before the restart.
+    pub fn open<P: AsRef<Path>>(dir: P, policy: Box<dyn MatchingPolicy>, debug: bool) -> Result<Journaled, JournalError> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        let (mut engine, mut sequence) = match snapshots(&dir)?.pop() {
+            Some((sequence, path)) => (Engine::restore(Snapshot::load(path)?, policy, debug), sequence),
+            None => (Engine::with_policy(policy, debug), 0),
+        };
+
+        let (journal, records) = Journal::open(dir.join(JOURNAL_FILE))?;
+        for record in records {
+            // Records already covered by the snapshot survive when a crash hit between checkpoint and truncate.
+            if record.sequence > sequence {
+                engine.apply(record.command);
+                sequence = record.sequence;
+            }
+        }
+
+        Ok(Journaled { engine, journal, dir, sequence })
+    }
+
+    pub fn set_sync(&mut self, sync: bool) {
+        self.journal.set_sync(sync);
+    }
+
+    pub fn apply(&mut self, command: Command) -> Result<Applied, JournalError> {
+        let record = JournalRecord { sequence: self.sequence + 1, command };
+        self.journal.append(&record)?;
+
+        self.sequence = record.sequence;
+        Ok(self.engine.apply(record.command))
+    }
+
+    // Snapshots the engine and empties the journal, so recovery only replays what comes after.
+    pub fn checkpoint(&mut self) -> Result<(), JournalError> {
+        let path = self.dir.join(format!("snapshot-{}.json", self.sequence));
+        self.engine.snapshot().save(&path)?;
+
+        self.journal.truncate()?;
+
+        for (sequence, old) in snapshots(&self.dir)? {
+            if sequence < self.sequence {
+                fs::remove_file(old)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn engine(&self) -> &Engine {
+        &self.engine
+    }
+
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+}
@@ -0,0 +1,148 @@
+#[cfg(test)]
+mod journal_tests {
+
+    use std::fs::{self, OpenOptions};
+    use std::io::Write;
+    use std::path::PathBuf;
+    use crate::types::Order;
+    use crate::engine::engine::Engine;
+    use crate::engine::matching::Fifo;
+    use crate::engine::command::{Command, Applied};
+    use crate::engine::session::Phase;
+    use crate::journal::journal::JournalError;
+    use crate::journal::recovery::Journaled;
+    use crate::feed::feed::get_raw_feed;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("orderbook_journal_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn open(dir: &PathBuf) -> Journaled {
+        let mut service = Journaled::open(dir, Box::new(Fifo), false).unwrap();
+        service.set_sync(false);
+        service
+    }
+
+    fn commands() -> Vec<Command> {
+        get_raw_feed().unwrap().into_iter().take(2000).map(|order| {
+            if order.price == 0 { Command::Cancel(order.size) } else { Command::Limit(order) }
+        }).collect()
+    }
+
+    fn state(engine: &Engine) -> String {
+        serde_json::to_string(&engine.snapshot()).unwrap()
+    }
+
+    #[test]
+    fn test_recover_from_journal() {
+        let dir = scratch_dir("replay");
+        let mut reference = Engine::new();
+
+        let mut service = open(&dir);
+        for command in commands() {
+            service.apply(command.clone()).unwrap();
+            reference.apply(command);
+        }
+        drop(service);
+
+        let mut recovered = open(&dir);
+        assert_eq!(recovered.sequence(), 2000);
+        assert_eq!(state(recovered.engine()), state(&reference));
+
+        // Ids carry on where the first run stopped.
+        let order = Order {symbol: String::from("SYM"), trader: String::from("ID1"), side: 0, price: 1, size: 1};
+        assert_eq!(recovered.apply(Command::Limit(order.clone())).unwrap(), Applied::Order(reference.try_limit_order(order)));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_recover_from_checkpoint() {
+        let dir = scratch_dir("checkpoint");
+        let mut reference = Engine::new();
+        let commands = commands();
+
+        let mut service = open(&dir);
+        for (idx, command) in commands.into_iter().enumerate() {
+            if idx == 1000 {
+                service.checkpoint().unwrap();
+            }
+            if idx == 1500 {
+                service.apply(Command::Transition(Phase::Halted)).unwrap();
+                reference.apply(Command::Transition(Phase::Halted));
+                service.apply(Command::Transition(Phase::Auction)).unwrap();
+                reference.apply(Command::Transition(Phase::Auction));
+            }
+            service.apply(command.clone()).unwrap();
+            reference.apply(command);
+        }
+        drop(service);
+
+        let recovered = open(&dir);
+        assert_eq!(recovered.sequence(), 2002);
+        assert_eq!(recovered.engine().phase(), Phase::Auction);
+        assert_eq!(state(recovered.engine()), state(&reference));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_torn_final_record() {
+        let dir = scratch_dir("torn");
+        let mut reference = Engine::new();
+
+        let mut service = open(&dir);
+        for command in commands().into_iter().take(100) {
+            service.apply(command.clone()).unwrap();
+            reference.apply(command);
+        }
+        drop(service);
+
+        // A crash halfway through writing the next record.
+        let mut file = OpenOptions::new().append(true).open(dir.join("journal.log")).unwrap();
+        file.write_all(&[200, 0, 0, 0, 1, 2, 3, 4, b'{']).unwrap();
+        drop(file);
+
+        let mut recovered = open(&dir);
+        assert_eq!(recovered.sequence(), 100);
+        assert_eq!(state(recovered.engine()), state(&reference));
+
+        // The torn bytes are gone, new records follow the last good one.
+        recovered.apply(Command::Cancel(1)).unwrap();
+        drop(recovered);
+        assert_eq!(open(&dir).sequence(), 101);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_corrupt_record() {
+        let dir = scratch_dir("corrupt");
+
+        let mut service = open(&dir);
+        for command in commands().into_iter().take(10) {
+            service.apply(command).unwrap();
+        }
+        drop(service);
+
+        let path = dir.join("journal.log");
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[10] ^= 0xFF;
+        fs::write(&path, bytes).unwrap();
+
+        assert!(matches!(Journaled::open(&dir, Box::new(Fifo), false), Err(JournalError::Corrupt { offset: 0 })));
+
+        // A length running past the end of the file, with intact records after it, is not a torn write.
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[10] ^= 0xFF;
+        let second = 8 + u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+        bytes[second + 3] = 0x7F;
+        fs::write(&path, &bytes).unwrap();
+        assert!(matches!(Journaled::open(&dir, Box::new(Fifo), false), Err(JournalError::Corrupt { offset }) if offset == second as u64));
+        assert_eq!(fs::read(&path).unwrap(), bytes);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
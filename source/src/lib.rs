@@ -0,0 +1,9 @@
+#![allow(clippy::module_inception)]
+
+pub mod types;
+pub mod engine;
+pub mod feed;
+pub mod score;
+pub mod journal;
+pub mod replay;
+pub mod fix;
@@ -1,13 +1,369 @@
-mod types;
-mod engine;
-mod feed;
-mod score;
+/*
+    high_frequency_order_book [--engine current|baseline|winning] [--feed <path> [--stock <symbol>] | --profile <name>]
+                              [--batch-size <n>] [--replays <n>] [--warmup <n>] [--format table|json] [--batches-only]
+                              [--deny-allocations] [--baseline <json> [--tolerance <percent>] [--alpha <p>]]
+                              [--cpu <n>] [--realtime <priority>] [--cold] [--outliers <n>]
 
-use crate::score::score::playback;
-use crate::feed::feed::get_raw_feed;
+    Scores an engine as cpp_reference/score.cpp does (see src/score/latency.rs), by default the current
+    engine on score_feed.csv in batches of 10 over 200 replays. Warmup replays run first and are not
+    measured. --cold evicts the caches before every replay, warmup included, for worst case numbers
+    (src/score/cache.rs). Unless --batches-only, a second run times every message on its own for the tail percentiles
+    and a breakdown by operation: resting and crossing limit orders, cancels that hit and missed (src/score/operations.rs).
+    Feeds ending in .bin are read as binary feeds, .itch as ITCH captures of the --stock they require, others as v1 or v2 csv.
+    --profile scores a generated workload from src/score/profiles.rs instead of a feed.
+
+    Built with --features count-allocations, a last untimed replay counts the engine's allocations per
+    message and per batch (src/score/allocations.rs). --deny-allocations then exits 1 if any message allocated.
+
+    --baseline compares the run to a report saved with --format json (src/score/regression.rs) and exits 1
+    if the score, the p99 batch latency or the allocations per message regressed beyond the tolerance
+    (10% by default), the timings only when the statistical test is significant at alpha (0.01).
+
+    On Linux --cpu pins the scorer to one CPU and --realtime asks for SCHED_FIFO at that priority, a refused
+    request only being warned about. The report starts with the environment (src/score/environment.rs).
+
+    Outlying batches are listed with their replay and feed offset, the slowest --outliers of them (10 by
+    default) and the offsets slow in more than one replay (src/score/outliers.rs). They are never dropped.
+*/
+
+use std::env;
+use std::path::Path;
+use std::process;
+use serde::Serialize;
+use high_frequency_order_book::engine::{baseline, engine, winning};
+use high_frequency_order_book::feed::binary::BinaryFeed;
+use high_frequency_order_book::feed::commands::load_commands;
+use high_frequency_order_book::feed::feed::{FlowSource, Mode, SCORE_FEED};
+use high_frequency_order_book::feed::itch::{load_capture, ItchConfig};
+use high_frequency_order_book::score::allocations::{count_engine, AllocationReport};
+use high_frequency_order_book::score::engines::MatchingEngine;
+use high_frequency_order_book::score::environment::{environment, pin_to_cpu, request_realtime, Environment};
+use high_frequency_order_book::score::histogram::{Histogram, Summary};
+use high_frequency_order_book::score::cache::CacheEvictor;
+use high_frequency_order_book::score::latency::{measure_between, score, Score, REPLAYS};
+use high_frequency_order_book::score::outliers::{outliers, OutlierReport, FENCE};
+use high_frequency_order_book::score::operations::{breakdown, classify, OperationLatency};
+use high_frequency_order_book::score::profiles::{profile, profiles};
+use high_frequency_order_book::score::regression::{compare, environment_differences, load_baseline, Check, Gate, Results};
+use high_frequency_order_book::score::score::MSG_BATCH_SIZE;
+
+const USAGE: &str = "usage: high_frequency_order_book [--engine current|baseline|winning] [--feed <path> [--stock <symbol>] | --profile <name>] \
+[--batch-size <n>] [--replays <n>] [--warmup <n>] [--format table|json] [--batches-only] [--deny-allocations] \
+[--baseline <json> [--tolerance <percent>] [--alpha <p>]] [--cpu <n>] [--realtime <priority>] [--cold] [--outliers <n>]";
+const ENGINES: [&str; 3] = [engine::Engine::NAME, baseline::Engine::NAME, winning::Engine::NAME];
+const HISTOGRAM_WIDTH: usize = 50;
+const PROFILE_SEED: u64 = 1;
+const COUNTING: bool = cfg!(feature = "count-allocations");
+
+#[cfg(feature = "count-allocations")]
+#[global_allocator]
+static ALLOCATOR: high_frequency_order_book::score::allocations::CountingAllocator =
+    high_frequency_order_book::score::allocations::CountingAllocator;
+
+struct Options {
+    engine: String,
+    feed: String,
+    stock: Option<String>,
+    profile: Option<String>,
+    batch_size: usize,
+    replays: usize,
+    warmup: usize,
+    json: bool,
+    messages: bool,
+    deny_allocations: bool,
+    baseline: Option<String>,
+    gate: Gate,
+    cpu: Option<usize>,
+    realtime: Option<i32>,
+    cold: bool,
+    outliers: usize,
+}
+
+#[derive(Serialize)]
+struct Report {
+    engine: String,
+    feed: String,
+    messages: usize,
+    batch_size: usize,
+    replays: usize,
+    warmup: usize,
+    cold: bool,
+    #[serde(flatten)]
+    score: Score,
+    batch_latency: Summary,
+    message_latency: Option<Summary>,
+    operations: Option<Vec<OperationLatency>>,
+    outliers: OutlierReport,
+    allocations: Option<AllocationReport>,
+    environment: Environment,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    checks: Vec<Check>,
+    #[serde(skip)]
+    histograms: Vec<(&'static str, Histogram)>,
+    #[serde(skip)]
+    batches: Vec<u64>,
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(2);
+}
+
+fn number(flag: &str, value: &str) -> usize {
+    value.parse().ok().filter(|n| *n > 0 || flag == "--warmup")
+        .unwrap_or_else(|| fail(&format!("{} takes a {}number", flag, if flag == "--warmup" { "" } else { "positive " })))
+}
+
+fn decimal(flag: &str, value: &str, valid: fn(&f64) -> bool, expected: &str) -> f64 {
+    value.parse().ok().filter(valid).unwrap_or_else(|| fail(&format!("{} takes {}", flag, expected)))
+}
+
+fn parse_options() -> Options {
+    let mut options = Options {
+        engine: String::from(engine::Engine::NAME),
+        feed: String::from(SCORE_FEED),
+        stock: None,
+        profile: None,
+        batch_size: MSG_BATCH_SIZE,
+        replays: REPLAYS,
+        warmup: 0,
+        json: false,
+        messages: true,
+        deny_allocations: false,
+        baseline: None,
+        gate: Gate::new(),
+        cpu: None,
+        realtime: None,
+        cold: false,
+        outliers: 10,
+    };
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut rest = args.iter();
+    while let Some(flag) = rest.next() {
+        match flag.as_str() {
+            "--batches-only" => options.messages = false,
+            "--cold" => options.cold = true,
+            "--deny-allocations" if COUNTING => options.deny_allocations = true,
+            "--deny-allocations" => fail("--deny-allocations needs a build with --features count-allocations"),
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ => {
+                let value = rest.next().unwrap_or_else(|| fail(USAGE));
+                match flag.as_str() {
+                    "--engine" if ENGINES.contains(&value.as_str()) => options.engine = value.clone(),
+                    "--engine" => fail(&format!("unknown engine '{}', expected one of {}", value, ENGINES.join(", "))),
+                    "--feed" => options.feed = value.clone(),
+                    "--stock" => options.stock = Some(value.clone()),
+                    "--profile" if profile(value, PROFILE_SEED).is_some() => options.profile = Some(value.clone()),
+                    "--profile" => {
+                        let names: Vec<&str> = profiles(PROFILE_SEED).iter().map(|x| x.name).collect();
+                        fail(&format!("unknown profile '{}', expected one of {}", value, names.join(", ")))
+                    }
+                    "--batch-size" => options.batch_size = number(flag, value),
+                    "--replays" => options.replays = number(flag, value),
+                    "--warmup" => options.warmup = number(flag, value),
+                    "--outliers" => options.outliers = value.parse().unwrap_or_else(|_| fail("--outliers takes a number")),
+                    "--baseline" => options.baseline = Some(value.clone()),
+                    "--cpu" => options.cpu = Some(value.parse().unwrap_or_else(|_| fail("--cpu takes a cpu number"))),
+                    "--realtime" => options.realtime = Some(value.parse().ok().filter(|x| (1..=99).contains(x))
+                        .unwrap_or_else(|| fail("--realtime takes a priority from 1 to 99"))),
+                    "--tolerance" => options.gate.tolerance = decimal(flag, value, |x| *x >= 0.0, "a percentage") / 100.0,
+                    "--alpha" => options.gate.alpha = decimal(flag, value, |x| *x > 0.0 && *x < 1.0, "a probability"),
+                    "--format" => options.json = match value.as_str() {
+                        "table" => false,
+                        "json" => true,
+                        _ => fail("--format takes table or json"),
+                    },
+                    _ => fail(USAGE),
+                }
+            }
+        }
+    }
+    // One book per engine, so a capture has to be narrowed to a single stock.
+    if options.profile.is_none() && options.feed.ends_with(".itch") && options.stock.is_none() {
+        fail("--feed with an .itch capture needs --stock <symbol>");
+    }
+    options
+}
+
+fn run<E: MatchingEngine, F: FlowSource + ?Sized>(options: &Options, flow: &F) -> Report {
+    let mut evictor = if options.cold { Some(CacheEvictor::new()) } else { None };
+    let mut between = || if let Some(evictor) = evictor.as_mut() { evictor.evict() };
+    measure_between::<E, F, _>(flow, options.warmup, options.batch_size, &mut between);
+
+    let batches = measure_between::<E, F, _>(flow, options.replays, options.batch_size, &mut between);
+    let mut histograms = vec![("batch", Histogram::from_samples(&batches))];
+    let mut operations = None;
+    if options.messages {
+        let messages = measure_between::<E, F, _>(flow, options.replays, 1, &mut between);
+        histograms.push(("message", Histogram::from_samples(&messages)));
+        operations = Some(breakdown(&classify(flow), &messages));
+    }
+
+    Report {
+        engine: options.engine.clone(),
+        feed: options.profile.as_ref().map_or_else(|| options.feed.clone(), |x| format!("profile {}", x)),
+        messages: flow.messages(),
+        batch_size: options.batch_size,
+        replays: options.replays,
+        warmup: options.warmup,
+        cold: options.cold,
+        score: score(&batches),
+        batch_latency: histograms[0].1.summary(),
+        message_latency: histograms.get(1).map(|x| x.1.summary()),
+        operations,
+        outliers: outliers(&batches, flow.messages(), options.batch_size, options.outliers),
+        allocations: if COUNTING { Some(count_engine::<E, F>(flow, options.batch_size)) } else { None },
+        environment: environment(),
+        checks: Vec::new(),
+        histograms,
+        batches,
+    }
+}
+
+fn run_engine<F: FlowSource + ?Sized>(options: &Options, flow: &F) -> Report {
+    match options.engine.as_str() {
+        "baseline" => run::<baseline::Engine, F>(options, flow),
+        "winning" => run::<winning::Engine, F>(options, flow),
+        _ => run::<engine::Engine, F>(options, flow),
+    }
+}
+
+fn check_baseline(path: &str, report: &Report, gate: &Gate) -> Vec<Check> {
+    let baseline = load_baseline(path).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)));
+    let current = Results {
+        engine: report.engine.clone(),
+        feed: report.feed.clone(),
+        messages: report.messages,
+        batch_size: report.batch_size,
+        cold: report.cold,
+        score: report.score,
+        batch_latency: report.batch_latency,
+        allocations: report.allocations.clone(),
+        environment: Some(report.environment.clone()),
+    };
+
+    // Feeds are compared by file name, the default feed's path depends on the checkout.
+    let workload = |x: &Results| (x.engine.clone(), Path::new(&x.feed).file_name().map(|x| x.to_owned()), x.messages, x.batch_size, x.cold);
+    if workload(&baseline) != workload(&current) {
+        fail(&format!("{} scored {} on {} in batches of {}{}, not comparable with this run",
+            path, baseline.engine, baseline.feed, baseline.batch_size, if baseline.cold { " with cold caches" } else { "" }));
+    }
+    if let Some(environment) = &baseline.environment {
+        for difference in environment_differences(environment, &report.environment) {
+            eprintln!("warning: measured on a different machine than {}: {}", path, difference);
+        }
+    }
+    compare(&baseline, &current, &report.batches, gate)
+}
+
+fn print_table(report: &Report) {
+    println!("{}", report.environment);
+    println!();
+    let rows = [
+        ("engine", report.engine.clone()),
+        ("feed", format!("{} ({} messages)", report.feed, report.messages)),
+        ("batch size", report.batch_size.to_string()),
+        ("replays", format!("{} after {} warmup{}", report.replays, report.warmup, if report.cold { ", caches evicted" } else { "" })),
+        ("samples", report.score.samples.to_string()),
+        ("mean", format!("{:.2} ns", report.score.mean)),
+        ("sd", format!("{:.2} ns", report.score.sd)),
+        ("score", format!("{:.2}", report.score.score)),
+    ];
+    for (name, value) in rows.iter() {
+        println!("{:<12} {}", name, value);
+    }
+
+    if let Some(allocations) = &report.allocations {
+        println!();
+        println!("{:<12} {:.3} per message ({:.1} bytes), {:.3} per batch ({:.1} bytes), at most {} in a batch",
+            "allocations", allocations.per_message, allocations.bytes_per_message, allocations.per_batch,
+            allocations.bytes_per_batch, allocations.max_per_batch);
+        println!("{:<12} {} of {} messages allocated", "", allocations.allocating_messages, allocations.messages);
+    }
+
+    for (name, histogram) in &report.histograms {
+        let summary = histogram.summary();
+        println!();
+        println!("{:<12} p50 {} | p90 {} | p99 {} | p99.9 {} | max {} (ns)", format!("{} latency", name),
+            summary.p50, summary.p90, summary.p99, summary.p999, summary.max);
+        print!("{}", histogram.render(HISTOGRAM_WIDTH));
+    }
+
+    if let Some(operations) = &report.operations {
+        println!();
+        println!("{:<36} {:>9} {:>7} {:>9} {:>9} {:>9} {:>10}", "operation", "messages", "time", "mean", "p50", "p99", "max (ns)");
+        for operation in operations {
+            println!("{:<36} {:>9} {:>6.1}% {:>9.1} {:>9} {:>9} {:>10}", operation.label, operation.latency.count / report.replays as u64,
+                operation.share * 100.0, operation.mean, operation.latency.p50, operation.latency.p99, operation.latency.max);
+        }
+    }
+
+    let outliers = &report.outliers;
+    println!();
+    println!("{:<12} {} batches above {} ns (Q3 + {} IQR, median {} ns)", "outliers", outliers.count, outliers.threshold, FENCE, outliers.median);
+    for outlier in &outliers.worst {
+        println!("{:<12} {} ns in replay {} at message {}", "", outlier.latency, outlier.replay, outlier.offset);
+    }
+    for recurring in &outliers.recurring {
+        println!("{:<12} message {} slow in {} replays", "recurring", recurring.offset, recurring.replays);
+    }
+
+    if !report.checks.is_empty() {
+        println!();
+        for check in &report.checks {
+            println!("{}", check);
+        }
+    }
+}
 
 fn main() {
-    let mut flow = get_raw_feed();
+    let options = parse_options();
+
+    if let Some(cpu) = options.cpu {
+        pin_to_cpu(cpu).unwrap_or_else(|e| fail(&format!("cannot pin to cpu {}: {}", cpu, e)));
+    }
+    if let Some(priority) = options.realtime {
+        if let Err(e) = request_realtime(priority) {
+            eprintln!("real-time scheduling refused, scoring at normal priority: {}", e);
+        }
+    }
+
+    let mut report = if let Some(name) = &options.profile {
+        let commands = profile(name, PROFILE_SEED).map(|x| x.commands()).unwrap_or_default();
+        run_engine(&options, commands.as_slice())
+    } else if options.feed.ends_with(".bin") {
+        let feed = BinaryFeed::open(&options.feed).unwrap_or_else(|e| fail(&format!("{}: {}", options.feed, e)));
+        run_engine(&options, &feed)
+    } else if options.feed.ends_with(".itch") {
+        let commands = load_capture(&options.feed, &ItchConfig { stock: options.stock.clone(), ..ItchConfig::new() }).unwrap_or_else(|e| fail(&format!("{}: {}", options.feed, e)));
+        run_engine(&options, commands.as_slice())
+    } else {
+        let feed = load_commands(&options.feed, Mode::Strict).unwrap_or_else(|e| fail(&e.to_string()));
+        run_engine(&options, feed.commands.as_slice())
+    };
+    if let Some(path) = &options.baseline {
+        report.checks = check_baseline(path, &report, &options.gate);
+    }
+
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&report).unwrap_or_else(|e| fail(&e.to_string())));
+    } else {
+        print_table(&report);
+    }
+
+    let allocating = report.allocations.as_ref().map_or(0, |x| x.allocating_messages);
+    if options.deny_allocations && allocating > 0 {
+        eprintln!("{} messages allocated on the hot path", allocating);
+        process::exit(1);
+    }
 
-    playback(&mut flow);
+    let regressed: Vec<&str> = report.checks.iter().filter(|x| x.regressed).map(|x| x.metric).collect();
+    if !regressed.is_empty() {
+        eprintln!("regressed against {}: {}", options.baseline.as_deref().unwrap_or_default(), regressed.join(", "));
+        process::exit(1);
+    }
 }
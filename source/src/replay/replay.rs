@@ -0,0 +1,194 @@
+/*
+    Replays a command stream and digests everything the engine produces, so two engines (or two
+    versions of engine.rs) can be shown to behave identically.
+
+    The digest file is a csv of checkpoints
+        commands,outputs,book
+    where outputs is a rolling digest of every response and execution up to that command and book
+    a digest of both sides of the book at that point, both as hex FNV-1a 64.
+*/
+
+use std::fmt;
+use std::path::Path;
+use serde::{Serialize, Deserialize};
+use crate::types::{Order, Execution};
+use crate::engine::engine::{Engine, OrderIn};
+use crate::engine::command::{Command, Applied};
+use crate::engine::session::Reject;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+// FNV-1a 64, not cryptographic but stable across platforms and builds.
+#[derive(Clone, Copy, Debug)]
+pub struct Digest(u64);
+
+impl Digest {
+
+    pub fn new() -> Digest {
+        Digest(FNV_OFFSET)
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.update(&value.to_le_bytes());
+    }
+
+    pub fn str(&mut self, value: &str) {
+        self.u64(value.len() as u64);
+        self.update(value.as_bytes());
+    }
+
+    pub fn order(&mut self, order: &Order) {
+        self.str(&order.symbol);
+        self.str(&order.trader);
+        self.u64(order.side as u64);
+        self.u64(order.price as u64);
+        self.u64(order.size);
+    }
+
+    pub fn value(&self) -> u64 {
+        self.0
+    }
+}
+
+impl Default for Digest {
+    fn default() -> Self {
+        Digest::new()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    // Number of commands applied.
+    pub commands: u64,
+    pub outputs: String,
+    pub book: String,
+}
+
+#[derive(Debug)]
+pub enum Divergence {
+    // First checkpoint whose digests differ.
+    Checkpoint { expected: Checkpoint, actual: Checkpoint },
+    // The runs recorded a different number of checkpoints.
+    Length { expected: usize, actual: usize },
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Divergence::Checkpoint { expected, actual } => write!(f,
+                "diverged by command {}: outputs {} (expected {}), book {} (expected {})",
+                actual.commands, actual.outputs, expected.outputs, actual.book, expected.book),
+            Divergence::Length { expected, actual } => write!(f, "{} checkpoints, expected {}", actual, expected),
+        }
+    }
+}
+
+fn hex(value: u64) -> String {
+    format!("{:016x}", value)
+}
+
+fn digest_reject(digest: &mut Digest, reject: &Reject) {
+    digest.str(&reject.to_string());
+}
+
+fn digest_applied(digest: &mut Digest, applied: &Applied) {
+    match applied {
+        Applied::Order(Ok(id)) => { digest.u64(0); digest.u64(*id); }
+        Applied::Order(Err(reject)) => { digest.u64(1); digest_reject(digest, reject); }
+        Applied::Cancel(Ok(())) => digest.u64(2),
+        Applied::Cancel(Err(reject)) => { digest.u64(3); digest_reject(digest, reject); }
+        Applied::Transition(Ok(Some(uncrossed))) => {
+            digest.u64(4);
+            digest.u64(uncrossed.price as u64);
+            digest.u64(uncrossed.volume);
+        }
+        Applied::Transition(Ok(None)) => digest.u64(5),
+        Applied::Transition(Err(_)) => digest.u64(6),
+        Applied::Configured => digest.u64(7),
+        Applied::Amend(Ok(())) => digest.u64(8),
+        Applied::Amend(Err(reject)) => { digest.u64(9); digest_reject(digest, reject); }
+    }
+}
+
+fn digest_executions(digest: &mut Digest, executions: &[Execution]) {
+    for exec in executions {
+        digest.order(exec);
+    }
+}
+
+fn digest_side(digest: &mut Digest, side: &[OrderIn]) {
+    digest.u64(side.len() as u64);
+    for resting in side {
+        digest.u64(resting.id);
+        digest.order(&resting.order);
+    }
+}
+
+pub fn book_digest(engine: &Engine) -> u64 {
+    let snapshot = engine.snapshot();
+    let mut digest = Digest::new();
+    digest_side(&mut digest, &snapshot.bids);
+    digest_side(&mut digest, &snapshot.asks);
+    digest.value()
+}
+
+// Applies every command to a logging engine, recording a checkpoint every `every` commands and after the last.
+pub fn replay<I: IntoIterator<Item = Command>>(commands: I, every: u64) -> Vec<Checkpoint> {
+    let mut engine = Engine::new_debug();
+    let mut outputs = Digest::new();
+    let mut checkpoints = Vec::new();
+    let mut count = 0;
+
+    for command in commands {
+        let applied = engine.apply(command);
+        digest_applied(&mut outputs, &applied);
+        digest_executions(&mut outputs, &engine.execution_log);
+        engine.execution_log.clear();
+
+        count += 1;
+        if count % every == 0 {
+            checkpoints.push(Checkpoint { commands: count, outputs: hex(outputs.value()), book: hex(book_digest(&engine)) });
+        }
+    }
+
+    if count % every != 0 || count == 0 {
+        checkpoints.push(Checkpoint { commands: count, outputs: hex(outputs.value()), book: hex(book_digest(&engine)) });
+    }
+
+    checkpoints
+}
+
+pub fn verify(expected: &[Checkpoint], actual: &[Checkpoint]) -> Result<(), Divergence> {
+    for (e, a) in expected.iter().zip(actual.iter()) {
+        if e != a {
+            return Err(Divergence::Checkpoint { expected: e.clone(), actual: a.clone() });
+        }
+    }
+
+    if expected.len() != actual.len() {
+        return Err(Divergence::Length { expected: expected.len(), actual: actual.len() });
+    }
+    Ok(())
+}
+
+pub fn write_digests<P: AsRef<Path>>(path: P, checkpoints: &[Checkpoint]) -> Result<(), csv::Error> {
+    let mut writer = csv::Writer::from_path(path)?;
+    for checkpoint in checkpoints {
+        writer.serialize(checkpoint)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn read_digests<P: AsRef<Path>>(path: P) -> Result<Vec<Checkpoint>, csv::Error> {
+    let mut reader = csv::Reader::from_path(path)?;
+    reader.deserialize().collect()
+}
@@ -0,0 +1,38 @@
+#[cfg(test)]
+mod replay_tests {
+
+    use crate::engine::command::Command;
+    use crate::feed::feed::{get_raw_feed, to_command};
+    use crate::replay::replay::{replay, verify, read_digests, Divergence};
+
+    fn score_commands() -> Vec<Command> {
+        get_raw_feed().unwrap().iter().map(to_command).collect()
+    }
+
+    #[test]
+    fn test_score_feed_matches_recorded_digest() {
+        let expected = read_digests("./data/score_feed.digest").unwrap();
+        let actual = replay(score_commands(), 1000);
+
+        if let Err(divergence) = verify(&expected, &actual) {
+            panic!("engine behaviour changed: {}", divergence);
+        }
+    }
+
+    #[test]
+    fn test_divergence_reported() {
+        let mut commands = score_commands();
+        let expected = replay(commands.clone(), 1000);
+
+        // Dropping one early cancel changes the book from there on.
+        let idx = commands.iter().position(|x| matches!(x, Command::Cancel(_))).unwrap();
+        commands.remove(idx);
+        commands.push(Command::Cancel(0));
+        let actual = replay(commands, 1000);
+
+        match verify(&expected, &actual) {
+            Err(Divergence::Checkpoint { expected, .. }) => assert_eq!(expected.commands, 1000),
+            other => panic!("expected a divergence at the first checkpoint, got {:?}", other),
+        }
+    }
+}
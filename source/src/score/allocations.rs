@@ -0,0 +1,124 @@
+/*
+    Allocation accounting for the scoring harness.
+
+    CountingAllocator wraps the system allocator and counts every allocation and the bytes asked for,
+    a realloc counting as a new allocation of its new size. It only counts once a binary installs it
+    with #[global_allocator], which the scoring CLI does when built with the count-allocations feature.
+    Commands are built before the counters are read, so only what the engine allocates is attributed.
+*/
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicU64, Ordering};
+use serde::{Deserialize, Serialize};
+use crate::feed::feed::FlowSource;
+use crate::score::engines::MatchingEngine;
+
+static COUNT: AtomicU64 = AtomicU64::new(0);
+static BYTES: AtomicU64 = AtomicU64::new(0);
+
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        record(layout.size());
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        record(layout.size());
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        record(new_size);
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+fn record(size: usize) {
+    COUNT.fetch_add(1, Ordering::Relaxed);
+    BYTES.fetch_add(size as u64, Ordering::Relaxed);
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Allocations {
+    pub count: u64,
+    pub bytes: u64,
+}
+
+// Totals since the process started, always zero unless CountingAllocator is installed.
+pub fn allocations() -> Allocations {
+    Allocations { count: COUNT.load(Ordering::Relaxed), bytes: BYTES.load(Ordering::Relaxed) }
+}
+
+impl Allocations {
+
+    pub fn since(&self, earlier: Allocations) -> Allocations {
+        Allocations { count: self.count - earlier.count, bytes: self.bytes - earlier.bytes }
+    }
+
+    fn add(&mut self, other: Allocations) {
+        self.count += other.count;
+        self.bytes += other.bytes;
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AllocationReport {
+    pub messages: usize,
+    pub batches: usize,
+    pub total: Allocations,
+    pub per_message: f64,
+    pub bytes_per_message: f64,
+    pub per_batch: f64,
+    pub bytes_per_batch: f64,
+    pub max_per_batch: u64,
+    // Messages that allocated at all, zero for an allocation free hot path.
+    pub allocating_messages: usize,
+}
+
+// One replay over the same batches measure_engine times, counting every message on its own.
+pub fn count_engine<E: MatchingEngine, F: FlowSource + ?Sized>(flow: &F, batch_size: usize) -> AllocationReport {
+    let batch_size = batch_size.max(1);
+    let batches = flow.messages().saturating_sub(1) / batch_size;
+    let messages = batches * batch_size;
+
+    let mut engine = E::create();
+    let mut total = Allocations::default();
+    let (mut allocating_messages, mut max_per_batch) = (0, 0);
+
+    for batch in 0..batches {
+        let mut in_batch = Allocations::default();
+        for idx in batch * batch_size..(batch + 1) * batch_size {
+            let command = flow.command(idx);
+
+            let before = allocations();
+            engine.submit(command);
+            let used = allocations().since(before);
+
+            if used.count > 0 {
+                allocating_messages += 1;
+            }
+            in_batch.add(used);
+        }
+        max_per_batch = max_per_batch.max(in_batch.count);
+        total.add(in_batch);
+    }
+
+    let per = |value: u64, over: usize| if over == 0 { 0.0 } else { value as f64 / over as f64 };
+    AllocationReport {
+        messages,
+        batches,
+        total,
+        per_message: per(total.count, messages),
+        bytes_per_message: per(total.bytes, messages),
+        per_batch: per(total.count, batches),
+        bytes_per_batch: per(total.bytes, batches),
+        max_per_batch,
+        allocating_messages,
+    }
+}
@@ -0,0 +1,68 @@
+/*
+    Cache eviction for cache-cold scoring.
+
+    Replaying one feed hundreds of times leaves the engine, its book and the feed in cache, so every
+    replay after the first measures the best case. Writing a buffer twice the size of the largest cache
+    between replays pushes all of them out, and the first batches of each replay then show the worst case.
+*/
+
+use std::fs;
+use std::hint::black_box;
+
+const LINE: usize = 64;
+// Used when sysfs does not say, larger than most last level caches.
+const DEFAULT_CACHE: usize = 32 << 20;
+
+pub struct CacheEvictor {
+    buffer: Vec<u8>,
+    pass: u8,
+}
+
+// Largest cache of cpu0 in bytes, from sizes like "32768K" under /sys/devices/system/cpu/cpu0/cache.
+pub fn largest_cache() -> Option<usize> {
+    let entries = fs::read_dir("/sys/devices/system/cpu/cpu0/cache").ok()?;
+    entries.filter_map(|x| x.ok())
+        .filter_map(|x| fs::read_to_string(x.path().join("size")).ok())
+        .filter_map(|x| parse_size(x.trim()))
+        .max()
+}
+
+pub fn parse_size(size: &str) -> Option<usize> {
+    let (digits, scale) = match size.chars().last()? {
+        'K' => (&size[..size.len() - 1], 1 << 10),
+        'M' => (&size[..size.len() - 1], 1 << 20),
+        'G' => (&size[..size.len() - 1], 1 << 30),
+        _ => (size, 1),
+    };
+    digits.parse::<usize>().ok().map(|x| x * scale)
+}
+
+impl CacheEvictor {
+
+    pub fn new() -> CacheEvictor {
+        CacheEvictor::with_size(2 * largest_cache().unwrap_or(DEFAULT_CACHE))
+    }
+
+    pub fn with_size(bytes: usize) -> CacheEvictor {
+        CacheEvictor { buffer: vec![0; bytes.max(LINE)], pass: 0 }
+    }
+
+    pub fn size(&self) -> usize {
+        self.buffer.len()
+    }
+
+    // Writes every cache line of the buffer, a different value each pass so no write is skipped.
+    pub fn evict(&mut self) {
+        self.pass = self.pass.wrapping_add(1);
+        for line in self.buffer.chunks_mut(LINE) {
+            line[0] = self.pass;
+        }
+        black_box(&self.buffer);
+    }
+}
+
+impl Default for CacheEvictor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
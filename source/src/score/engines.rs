@@ -0,0 +1,66 @@
+/*
+    The engines that can be scored, behind one interface so they replay identical input.
+
+    The example engines only take limit orders and cancels, other commands are ignored by them.
+    Their fills are not identical: the winning engine trades at the resting price, the others at the
+    incoming order's price. Only their speed is compared.
+*/
+
+use crate::engine::{baseline, engine, winning};
+use crate::engine::command::Command;
+
+pub trait MatchingEngine {
+    const NAME: &'static str;
+
+    fn create() -> Self;
+
+    fn submit(&mut self, command: Command);
+}
+
+impl MatchingEngine for engine::Engine {
+    const NAME: &'static str = "current";
+
+    fn create() -> Self {
+        engine::Engine::new()
+    }
+
+    fn submit(&mut self, command: Command) {
+        self.apply(command);
+    }
+}
+
+impl MatchingEngine for baseline::Engine {
+    const NAME: &'static str = "baseline";
+
+    fn create() -> Self {
+        baseline::Engine::new()
+    }
+
+    fn submit(&mut self, command: Command) {
+        match command {
+            Command::Limit(order) => {
+                self.limit_order(order);
+            }
+            Command::Cancel(id) => self.cancel(id),
+            _ => {}
+        }
+    }
+}
+
+impl MatchingEngine for winning::Engine {
+    const NAME: &'static str = "winning";
+
+    fn create() -> Self {
+        winning::Engine::new()
+    }
+
+    fn submit(&mut self, command: Command) {
+        match command {
+            Command::Limit(order) => {
+                self.limit_order(order);
+            }
+            Command::Cancel(id) => self.cancel(id),
+            _ => {}
+        }
+    }
+}
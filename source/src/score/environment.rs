@@ -0,0 +1,188 @@
+/*
+    The machine a score was measured on, and the controls that make it reproducible.
+
+    cpp_reference/score.cpp leaves print_cpuaffinity commented out and relies on isolcpus. Here the
+    scorer can pin itself to one CPU (ideally an isolated one), ask for SCHED_FIFO so other work cannot
+    preempt it, and report the CPU model, frequency governor and affinity mask next to the result.
+    All of it is Linux only: elsewhere pinning and real-time requests fail with Unsupported and the
+    report holds what could be found.
+*/
+
+use std::fmt;
+use std::fs;
+use std::io;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Environment {
+    pub cpu_model: Option<String>,
+    pub kernel: Option<String>,
+    // CPUs the scorer may run on, and the same set as a hex mask like score.cpp printed.
+    pub affinity: Vec<usize>,
+    pub affinity_mask: String,
+    // Governor of the first CPU in the affinity set.
+    pub governor: Option<String>,
+    // CPUs the kernel was booted to keep clear of other work.
+    pub isolated: Option<String>,
+    pub scheduler: String,
+}
+
+fn read_trimmed(path: &str) -> Option<String> {
+    fs::read_to_string(path).ok().map(|x| x.trim().to_string()).filter(|x| !x.is_empty())
+}
+
+fn cpu_model() -> Option<String> {
+    let cpuinfo = fs::read_to_string("/proc/cpuinfo").ok()?;
+    cpuinfo.lines()
+        .find(|x| x.starts_with("model name") || x.starts_with("Model"))
+        .and_then(|x| x.split(':').nth(1))
+        .map(|x| x.trim().to_string())
+}
+
+// "0-3,6" style list, as in /sys/devices/system/cpu/isolated.
+pub fn cpu_list(cpus: &[usize]) -> String {
+    let mut ranges: Vec<String> = Vec::new();
+    let mut idx = 0;
+    while idx < cpus.len() {
+        let start = idx;
+        while idx + 1 < cpus.len() && cpus[idx + 1] == cpus[idx] + 1 {
+            idx += 1;
+        }
+        ranges.push(if idx == start { cpus[start].to_string() } else { format!("{}-{}", cpus[start], cpus[idx]) });
+        idx += 1;
+    }
+    ranges.join(",")
+}
+
+pub fn cpu_mask(cpus: &[usize]) -> String {
+    let words = cpus.iter().max().map_or(1, |x| x / 32 + 1);
+    let mut mask = vec![0u32; words];
+    for cpu in cpus {
+        mask[cpu / 32] |= 1 << (cpu % 32);
+    }
+    mask.iter().rev().map(|x| format!("{:08x}", x)).collect::<Vec<_>>().join(",")
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::io;
+    use std::mem;
+
+    pub fn pin(cpu: usize) -> io::Result<()> {
+        if cpu >= libc::CPU_SETSIZE as usize {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("no cpu {}", cpu)));
+        }
+        // The calling thread, which is the only one the scorer runs.
+        unsafe {
+            let mut set: libc::cpu_set_t = mem::zeroed();
+            libc::CPU_SET(cpu, &mut set);
+            if libc::sched_setaffinity(0, mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn affinity() -> io::Result<Vec<usize>> {
+        unsafe {
+            let mut set: libc::cpu_set_t = mem::zeroed();
+            if libc::sched_getaffinity(0, mem::size_of::<libc::cpu_set_t>(), &mut set) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok((0..libc::CPU_SETSIZE as usize).filter(|x| libc::CPU_ISSET(*x, &set)).collect())
+        }
+    }
+
+    pub fn realtime(priority: i32) -> io::Result<()> {
+        let param = libc::sched_param { sched_priority: priority };
+        if unsafe { libc::sched_setscheduler(0, libc::SCHED_FIFO, &param) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    pub fn scheduler() -> String {
+        let mut param = libc::sched_param { sched_priority: 0 };
+        let policy = unsafe { libc::sched_getscheduler(0) };
+        unsafe { libc::sched_getparam(0, &mut param) };
+        match policy {
+            libc::SCHED_FIFO => format!("SCHED_FIFO {}", param.sched_priority),
+            libc::SCHED_RR => format!("SCHED_RR {}", param.sched_priority),
+            libc::SCHED_OTHER => String::from("SCHED_OTHER"),
+            libc::SCHED_BATCH => String::from("SCHED_BATCH"),
+            libc::SCHED_IDLE => String::from("SCHED_IDLE"),
+            _ => format!("policy {}", policy),
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod platform {
+    use std::io;
+
+    fn unsupported() -> io::Error {
+        io::Error::new(io::ErrorKind::Unsupported, "only supported on Linux")
+    }
+
+    pub fn pin(_cpu: usize) -> io::Result<()> {
+        Err(unsupported())
+    }
+
+    pub fn affinity() -> io::Result<Vec<usize>> {
+        Err(unsupported())
+    }
+
+    pub fn realtime(_priority: i32) -> io::Result<()> {
+        Err(unsupported())
+    }
+
+    pub fn scheduler() -> String {
+        String::from("unknown")
+    }
+}
+
+// Pins the calling thread to one CPU.
+pub fn pin_to_cpu(cpu: usize) -> io::Result<()> {
+    platform::pin(cpu)
+}
+
+pub fn affinity() -> io::Result<Vec<usize>> {
+    platform::affinity()
+}
+
+// SCHED_FIFO at priority 1 to 99, usually needs root or CAP_SYS_NICE. A spinning scorer at real-time
+// priority can starve its CPU, so pin it to an isolated one first.
+pub fn request_realtime(priority: i32) -> io::Result<()> {
+    if !(1..=99).contains(&priority) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "real-time priority is 1 to 99"));
+    }
+    platform::realtime(priority)
+}
+
+pub fn environment() -> Environment {
+    let affinity = affinity().unwrap_or_default();
+    let governor = affinity.first()
+        .and_then(|x| read_trimmed(&format!("/sys/devices/system/cpu/cpu{}/cpufreq/scaling_governor", x)));
+
+    Environment {
+        cpu_model: cpu_model(),
+        kernel: read_trimmed("/proc/sys/kernel/osrelease"),
+        affinity_mask: cpu_mask(&affinity),
+        affinity,
+        governor,
+        isolated: read_trimmed("/sys/devices/system/cpu/isolated"),
+        scheduler: platform::scheduler(),
+    }
+}
+
+impl fmt::Display for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unknown = |x: &Option<String>| x.clone().unwrap_or_else(|| String::from("unknown"));
+        writeln!(f, "{:<12} {}", "cpu", unknown(&self.cpu_model))?;
+        writeln!(f, "{:<12} {}", "kernel", unknown(&self.kernel))?;
+        writeln!(f, "{:<12} {} (mask {})", "affinity", cpu_list(&self.affinity), self.affinity_mask)?;
+        writeln!(f, "{:<12} {}", "governor", unknown(&self.governor))?;
+        writeln!(f, "{:<12} {}", "isolated", self.isolated.clone().unwrap_or_else(|| String::from("none")))?;
+        write!(f, "{:<12} {}", "scheduler", self.scheduler)
+    }
+}
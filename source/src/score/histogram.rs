@@ -0,0 +1,158 @@
+/*
+    Log-bucketed latency histogram.
+
+    Values below 2 * SUB_BUCKETS are counted exactly. Above that every power of two is split into
+    SUB_BUCKETS equal buckets, so a recorded value is known to within 1 / SUB_BUCKETS (6.25%) whatever
+    its size, in a fixed 976 counters.
+*/
+
+use std::fmt;
+use serde::{Deserialize, Serialize};
+
+pub const SUB_BUCKETS: u64 = 16;
+const EXACT: u64 = 2 * SUB_BUCKETS;
+const BUCKETS: usize = EXACT as usize + (64 - 5) * SUB_BUCKETS as usize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Summary {
+    pub count: u64,
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub p999: u64,
+    pub max: u64,
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "p50 = {}, p90 = {}, p99 = {}, p99.9 = {}, max = {} ({} samples)",
+            self.p50, self.p90, self.p99, self.p999, self.max, self.count)
+    }
+}
+
+#[derive(Clone)]
+pub struct Histogram {
+    counts: Vec<u64>,
+    count: u64,
+    sum: u128,
+    min: u64,
+    max: u64,
+}
+
+fn index(value: u64) -> usize {
+    if value < EXACT {
+        return value as usize;
+    }
+    let exponent = 63 - value.leading_zeros() as u64;
+    let shift = exponent - 4;
+    let sub = (value >> shift) - SUB_BUCKETS;
+    (EXACT + (exponent - 5) * SUB_BUCKETS + sub) as usize
+}
+
+// Smallest and largest value counted in bucket idx.
+fn bounds(idx: usize) -> (u64, u64) {
+    let idx = idx as u64;
+    if idx < EXACT {
+        return (idx, idx);
+    }
+    let (exponent, sub) = ((idx - EXACT) / SUB_BUCKETS + 5, (idx - EXACT) % SUB_BUCKETS);
+    let shift = exponent - 4;
+    let low = (SUB_BUCKETS + sub) << shift;
+    (low, low + ((1u64 << shift) - 1))
+}
+
+impl Histogram {
+
+    pub fn new() -> Histogram {
+        Histogram { counts: vec![0; BUCKETS], count: 0, sum: 0, min: u64::MAX, max: 0 }
+    }
+
+    pub fn from_samples(samples: &[u64]) -> Histogram {
+        let mut histogram = Histogram::new();
+        for sample in samples {
+            histogram.record(*sample);
+        }
+        histogram
+    }
+
+    pub fn record(&mut self, value: u64) {
+        self.counts[index(value)] += 1;
+        self.count += 1;
+        self.sum += value as u128;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn min(&self) -> u64 {
+        if self.count == 0 { 0 } else { self.min }
+    }
+
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+
+    pub fn mean(&self) -> f64 {
+        if self.count == 0 { 0.0 } else { self.sum as f64 / self.count as f64 }
+    }
+
+    // Upper bound of the bucket holding the sample at percentile (0 to 100), never above the maximum.
+    pub fn percentile(&self, percentile: f64) -> u64 {
+        if self.count == 0 {
+            return 0;
+        }
+        let rank = ((percentile / 100.0 * self.count as f64).ceil() as u64).clamp(1, self.count);
+
+        let mut seen = 0;
+        for (idx, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return bounds(idx).1.min(self.max);
+            }
+        }
+        self.max
+    }
+
+    pub fn summary(&self) -> Summary {
+        Summary {
+            count: self.count,
+            p50: self.percentile(50.0),
+            p90: self.percentile(90.0),
+            p99: self.percentile(99.0),
+            p999: self.percentile(99.9),
+            max: self.max,
+        }
+    }
+
+    // One row per power of two between the smallest and largest sample, bars scaled to width.
+    pub fn render(&self, width: usize) -> String {
+        if self.count == 0 {
+            return String::from("(no samples)\n");
+        }
+
+        let row = |value: u64| 64 - value.leading_zeros() as usize;
+        let mut rows = vec![0u64; 65];
+        for (idx, count) in self.counts.iter().enumerate().filter(|x| *x.1 > 0) {
+            rows[row(bounds(idx).0)] += count;
+        }
+
+        let (first, last) = (row(self.min()), row(self.max));
+        let peak = *rows[first..=last].iter().max().unwrap_or(&1);
+        let mut out = String::new();
+        for (idx, count) in rows.iter().enumerate().take(last + 1).skip(first) {
+            let (low, high) = if idx == 0 { (0, 0) } else { (1u64 << (idx - 1), (1u64 << (idx - 1)) * 2 - 1) };
+            let bar = (*count as f64 / peak as f64 * width as f64).round() as usize;
+            out.push_str(&format!("{:>12} - {:<12} |{:<width$}| {}\n", low, high, "#".repeat(bar), count, width = width));
+        }
+        out
+    }
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,94 @@
+/*
+    The QuantCup score, as computed by cpp_reference/score.cpp.
+
+    The feed is replayed REPLAYS times into a fresh engine. Each batch of MSG_BATCH_SIZE messages is
+    timed with CLOCK_MONOTONIC_RAW (a monotonic clock off Linux) and the score is 0.5 * (mean + sd)
+    of all batch latencies in nanoseconds, sd being the population standard deviation.
+    As in the original, messages after the last full batch start are never fed.
+*/
+
+use serde::{Deserialize, Serialize};
+use crate::engine::engine::Engine;
+use crate::feed::feed::FlowSource;
+use crate::score::score::{feed, MSG_BATCH_SIZE};
+use crate::score::engines::MatchingEngine;
+
+pub const REPLAYS: usize = 200;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Score {
+    pub samples: usize,
+    pub mean: f64,
+    pub sd: f64,
+    pub score: f64,
+}
+
+#[cfg(target_os = "linux")]
+pub fn now() -> u64 {
+    let mut time = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    // Cannot fail for a supported clock id and a valid pointer.
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC_RAW, &mut time) };
+    time.tv_sec as u64 * 1_000_000_000 + time.tv_nsec as u64
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn now() -> u64 {
+    use std::time::Instant;
+    thread_local!(static START: Instant = Instant::now());
+    START.with(|start| start.elapsed().as_nanos() as u64)
+}
+
+// Nanoseconds taken by every batch of every replay, replay by replay.
+pub fn measure<F: FlowSource + ?Sized>(flow: &F, replays: usize, batch_size: usize) -> Vec<u64> {
+    measure_engine::<Engine, F>(flow, replays, batch_size)
+}
+
+pub fn measure_engine<E: MatchingEngine, F: FlowSource + ?Sized>(flow: &F, replays: usize, batch_size: usize) -> Vec<u64> {
+    measure_between::<E, F, _>(flow, replays, batch_size, || ())
+}
+
+// As measure_engine, calling between once each fresh engine is created, before its replay starts.
+pub fn measure_between<E, F, B>(flow: &F, replays: usize, batch_size: usize, mut between: B) -> Vec<u64>
+where
+    E: MatchingEngine,
+    F: FlowSource + ?Sized,
+    B: FnMut(),
+{
+    let batches = flow.messages().saturating_sub(1) / batch_size.max(1);
+    let mut latencies = Vec::with_capacity(replays * batches);
+
+    for _ in 0..replays {
+        let mut engine = E::create();
+        between();
+
+        let mut batch = batch_size;
+        while batch < flow.messages() {
+            let begin = now();
+            feed(batch - batch_size, batch, &mut engine, flow);
+            let end = now();
+
+            latencies.push(end - begin);
+            batch += batch_size;
+        }
+    }
+
+    latencies
+}
+
+pub fn score(latencies: &[u64]) -> Score {
+    let samples = latencies.len();
+    if samples == 0 {
+        return Score { samples, mean: 0.0, sd: 0.0, score: 0.0 };
+    }
+
+    let mean = latencies.iter().map(|x| *x as f64).sum::<f64>() / samples as f64;
+    let variance = latencies.iter().map(|x| (*x as f64 - mean).powi(2)).sum::<f64>() / samples as f64;
+    let sd = variance.sqrt();
+
+    Score { samples, mean, sd, score: 0.5 * (mean + sd) }
+}
+
+// The competition score, REPLAYS replays of MSG_BATCH_SIZE message batches.
+pub fn latency_score<F: FlowSource + ?Sized>(flow: &F) -> Score {
+    score(&measure(flow, REPLAYS, MSG_BATCH_SIZE))
+}
@@ -1 +1,12 @@
-pub mod score;
\ No newline at end of file
+pub mod score;
+pub mod engines;
+pub mod latency;
+pub mod histogram;
+pub mod profiles;
+pub mod allocations;
+pub mod regression;
+pub mod environment;
+pub mod cache;
+pub mod outliers;
+pub mod operations;
+mod test;
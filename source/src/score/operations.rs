@@ -0,0 +1,136 @@
+/*
+    Per-operation latency, so a batch is no longer a black box.
+
+    Every message of a feed is classified once by replaying it into the current engine: a limit order
+    that rests without trading, a limit or market order that crosses (bucketed by the price levels and
+    resting orders it traded with, in powers of two), an order the engine rejected, a cancel that found
+    its order or one that missed, or anything else. Per-message latencies are then split by class.
+    The classes describe the feed, so they hold for any price-time engine even though the example
+    engines skip the commands they do not know.
+*/
+
+use std::fmt;
+use serde::Serialize;
+use crate::engine::command::{Applied, Command};
+use crate::engine::engine::Engine;
+use crate::feed::feed::FlowSource;
+use crate::score::histogram::{Histogram, Summary};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub enum Operation {
+    Resting,
+    // Lower bounds of the power of two buckets of levels and orders traded with.
+    Crossing { levels: usize, orders: usize },
+    Rejected,
+    CancelHit,
+    CancelMiss,
+    Other,
+}
+
+// 1, 2-3, 4-7 and so on, by the lower bound.
+pub fn bucket(n: usize) -> usize {
+    if n == 0 { 0 } else { 1 << (usize::BITS - 1 - n.leading_zeros()) }
+}
+
+fn range(low: usize) -> String {
+    if low <= 1 { low.to_string() } else { format!("{}-{}", low, low * 2 - 1) }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operation::Resting => write!(f, "resting limit"),
+            Operation::Crossing { levels, orders } => write!(f, "crossing levels {} orders {}", range(*levels), range(*orders)),
+            Operation::Rejected => write!(f, "rejected order"),
+            Operation::CancelHit => write!(f, "cancel hit"),
+            Operation::CancelMiss => write!(f, "cancel miss"),
+            Operation::Other => write!(f, "other"),
+        }
+    }
+}
+
+// What each message of the feed does to a fresh engine.
+pub fn classify<F: FlowSource + ?Sized>(flow: &F) -> Vec<Operation> {
+    let mut engine = Engine::new_debug();
+    let mut operations = Vec::with_capacity(flow.messages());
+    // Prices of the resting orders the incoming one could reach, in priority order.
+    let mut opposite = Vec::new();
+
+    for idx in 0..flow.messages() {
+        let command = flow.command(idx);
+        let is_limit = matches!(command, Command::Limit(_));
+        let is_market = matches!(command, Command::Market(_));
+        // Taken before the orders traded with leave the book, and only as far as the order crosses.
+        opposite.clear();
+        if let Command::Limit(order) | Command::Market(order) = &command {
+            let mut wanted = 0;
+            for resting in engine.book(order.side ^ 1) {
+                let price = resting.order.price;
+                let crosses = is_market || if order.side == 0 { order.price >= price } else { order.price <= price };
+                if wanted >= order.size || !crosses {
+                    break;
+                }
+                wanted += resting.order.size;
+                opposite.push(price);
+            }
+        }
+
+        engine.execution_log.clear();
+        let applied = engine.apply(command);
+        let orders = engine.execution_log.len() / 2;
+
+        operations.push(match applied {
+            Applied::Order(Ok(_)) if is_limit && orders == 0 => Operation::Resting,
+            Applied::Order(Ok(_)) if (is_limit || is_market) && orders > 0 => {
+                let traded = &opposite[..orders.min(opposite.len())];
+                let levels = 1 + traded.windows(2).filter(|x| x[0] != x[1]).count();
+                Operation::Crossing { levels: bucket(levels), orders: bucket(orders) }
+            }
+            Applied::Order(Err(_)) => Operation::Rejected,
+            Applied::Cancel(Ok(())) => Operation::CancelHit,
+            Applied::Cancel(Err(_)) => Operation::CancelMiss,
+            _ => Operation::Other,
+        });
+    }
+
+    operations
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct OperationLatency {
+    pub operation: Operation,
+    pub label: String,
+    pub mean: f64,
+    // Of all the time spent on messages, how much went on this operation.
+    pub share: f64,
+    #[serde(flatten)]
+    pub latency: Summary,
+}
+
+// Splits per-message latencies, replay by replay as measure_engine returns them with a batch size of 1.
+pub fn breakdown(operations: &[Operation], latencies: &[u64]) -> Vec<OperationLatency> {
+    // measure_engine never times the last message.
+    let messages = operations.len().saturating_sub(1).max(1);
+    let mut histograms: Vec<(Operation, Histogram)> = Vec::new();
+
+    for (idx, latency) in latencies.iter().enumerate() {
+        let operation = operations[idx % messages];
+        match histograms.binary_search_by(|x| x.0.cmp(&operation)) {
+            Ok(at) => histograms[at].1.record(*latency),
+            Err(at) => {
+                let mut histogram = Histogram::new();
+                histogram.record(*latency);
+                histograms.insert(at, (operation, histogram));
+            }
+        }
+    }
+
+    let total: f64 = histograms.iter().map(|x| x.1.mean() * x.1.count() as f64).sum();
+    histograms.into_iter().map(|(operation, histogram)| OperationLatency {
+        operation,
+        label: operation.to_string(),
+        mean: histogram.mean(),
+        share: if total == 0.0 { 0.0 } else { histogram.mean() * histogram.count() as f64 / total },
+        latency: histogram.summary(),
+    }).collect()
+}
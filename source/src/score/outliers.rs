@@ -0,0 +1,80 @@
+/*
+    Outlying batch latencies, reported with where in the feed they happened rather than dropped.
+
+    A batch is an outlier above Tukey's far out fence, Q3 + 3 * IQR of all batches. Each is located by
+    replay and by the offset of its first message in the feed. An offset that is slow in many replays
+    points at the engine, one slow replay at everything else on the machine.
+*/
+
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+
+pub const FENCE: f64 = 3.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Outlier {
+    pub replay: usize,
+    // First message of the batch in the feed.
+    pub offset: usize,
+    pub latency: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Recurring {
+    pub offset: usize,
+    // Replays in which the batch at offset was an outlier.
+    pub replays: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct OutlierReport {
+    pub median: u64,
+    pub threshold: u64,
+    pub count: usize,
+    // Slowest first.
+    pub worst: Vec<Outlier>,
+    // Most often outlying offsets first, only those seen in more than one replay.
+    pub recurring: Vec<Recurring>,
+}
+
+// Exact quantile of sorted values, nearest rank.
+fn quantile(sorted: &[u64], q: f64) -> u64 {
+    let rank = ((q * sorted.len() as f64).ceil() as usize).clamp(1, sorted.len());
+    sorted[rank - 1]
+}
+
+// Latencies as measure_engine returns them for a feed of messages, replay by replay.
+// At most shown outliers of each kind are listed.
+pub fn outliers(latencies: &[u64], messages: usize, batch_size: usize, shown: usize) -> OutlierReport {
+    if latencies.is_empty() {
+        return OutlierReport::default();
+    }
+    let mut sorted = latencies.to_vec();
+    sorted.sort_unstable();
+    let (q1, median, q3) = (quantile(&sorted, 0.25), quantile(&sorted, 0.5), quantile(&sorted, 0.75));
+    let threshold = q3 + (FENCE * (q3 - q1) as f64) as u64;
+
+    let batch_size = batch_size.max(1);
+    let batches = (messages.saturating_sub(1) / batch_size).max(1);
+    let mut found: Vec<Outlier> = latencies.iter().enumerate()
+        .filter(|x| *x.1 > threshold)
+        .map(|(idx, latency)| Outlier { replay: idx / batches, offset: idx % batches * batch_size, latency: *latency })
+        .collect();
+
+    let mut by_offset: HashMap<usize, usize> = HashMap::new();
+    for outlier in &found {
+        *by_offset.entry(outlier.offset).or_insert(0) += 1;
+    }
+    let mut recurring: Vec<Recurring> = by_offset.into_iter()
+        .filter(|x| x.1 > 1)
+        .map(|(offset, replays)| Recurring { offset, replays })
+        .collect();
+    recurring.sort_by(|a, b| b.replays.cmp(&a.replays).then(a.offset.cmp(&b.offset)));
+    recurring.truncate(shown);
+
+    let count = found.len();
+    found.sort_by(|a, b| b.latency.cmp(&a.latency).then(a.replay.cmp(&b.replay)));
+    found.truncate(shown);
+
+    OutlierReport { median, threshold, count, worst: found, recurring }
+}
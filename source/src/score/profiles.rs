@@ -0,0 +1,89 @@
+/*
+    Benchmark profiles, generated workloads that each stress one path of the engine.
+
+    The score feed mixes everything, so a change to cross(), queue() or cancel() is diluted by the
+    rest. Every profile keeps to limit orders and cancels, which all three engines understand.
+*/
+
+use crate::engine::command::Command;
+use crate::feed::generator::{generate, GeneratorConfig, SizeDistribution};
+
+pub const PROFILE_MESSAGES: usize = 20_000;
+
+pub struct Profile {
+    pub name: &'static str,
+    // The engine path the profile is meant to load.
+    pub stresses: &'static str,
+    pub config: GeneratorConfig,
+}
+
+impl Profile {
+
+    // Every profile's config is valid, which the tests check.
+    pub fn commands(&self) -> Vec<Command> {
+        generate(&self.config).expect("invalid profile")
+    }
+}
+
+fn base(seed: u64) -> GeneratorConfig {
+    GeneratorConfig {
+        messages: PROFILE_MESSAGES,
+        volatility: 0.0,
+        market_ratio: 0.0,
+        ..GeneratorConfig::new(seed)
+    }
+}
+
+pub fn profiles(seed: u64) -> Vec<Profile> {
+    vec![
+        // Thousands of resting orders over 2000 levels a side, rarely crossed.
+        Profile {
+            name: "deep_book",
+            stresses: "queue() into a deep book",
+            config: GeneratorConfig { depth: 2000, cancel_ratio: 0.1, aggressiveness: 0.01, ..base(seed) },
+        },
+        // Almost every other message is a cancel, of a book that still grows slowly.
+        Profile {
+            name: "cancel_heavy",
+            stresses: "cancel()",
+            config: GeneratorConfig { cancel_ratio: 0.45, aggressiveness: 0.0, ..base(seed) },
+        },
+        // Thin levels of single lots, swept by orders for 60 lots priced 50 levels through the mid.
+        Profile {
+            name: "sweeps",
+            stresses: "cross() over many levels",
+            config: GeneratorConfig {
+                depth: 50,
+                sizes: SizeDistribution::Fixed(100),
+                cancel_ratio: 0.05,
+                aggressiveness: 0.0,
+                sweep_ratio: 0.02,
+                sweep_size: 6_000,
+                ..base(seed)
+            },
+        },
+        // One bid and one ask level holding every order.
+        Profile {
+            name: "one_price",
+            stresses: "queue() and cancel() within a long level",
+            config: GeneratorConfig { depth: 1, cancel_ratio: 0.3, aggressiveness: 0.02, ..base(seed) },
+        },
+        // 40 levels a side, 500 ticks apart around the middle of the price range.
+        Profile {
+            name: "sparse_levels",
+            stresses: "price scans between distant levels",
+            config: GeneratorConfig {
+                mid: 32_768,
+                depth: 40,
+                spacing: 500,
+                cancel_ratio: 0.3,
+                aggressiveness: 0.05,
+                ..base(seed)
+            },
+        },
+    ]
+}
+
+pub fn profile(name: &str, seed: u64) -> Option<Profile> {
+    profiles(seed).into_iter().find(|x| x.name == name)
+}
@@ -0,0 +1,207 @@
+/*
+    Regression gate against stored results.
+
+    Baselines are the scoring CLI's own JSON report, so any run with --format json can be committed
+    as one. A metric regresses only when it is worse than the baseline by more than the tolerance and,
+    for timings, the difference is also significant at alpha:
+      score           z test that 0.5 * (mean + sd) itself grew, a slower mean and a wider spread alike
+      p99             binomial test that more than 1% of batches now exceed the tolerated p99
+      allocations     counted exactly, so compared without a test
+    The machine is not part of the gate, but a baseline measured on another CPU model or frequency
+    governor is warned about, as its timings say little about this run.
+    Both tests use the normal approximation, sound for the thousands of batches a replay produces.
+    The standard error of a score comes from the delta method, which needs the skewness and kurtosis of
+    the batch latencies. A report does not keep them, so the baseline is taken to have the current shape.
+*/
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader};
+use serde::{Deserialize, Serialize};
+use crate::score::allocations::AllocationReport;
+use crate::score::environment::Environment;
+use crate::score::histogram::Summary;
+use crate::score::latency::Score;
+
+// The parts of a scoring report the gate reads, other fields are ignored.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Results {
+    pub engine: String,
+    pub feed: String,
+    pub messages: usize,
+    pub batch_size: usize,
+    // Reports without it were measured warm.
+    #[serde(default)]
+    pub cold: bool,
+    #[serde(flatten)]
+    pub score: Score,
+    pub batch_latency: Summary,
+    pub allocations: Option<AllocationReport>,
+    // Reports from before the environment was recorded have none.
+    #[serde(default)]
+    pub environment: Option<Environment>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Gate {
+    // Relative worsening allowed, 0.10 for 10%. p99 moves in steps of a histogram bucket, about 6%.
+    pub tolerance: f64,
+    // Significance level of the statistical tests.
+    pub alpha: f64,
+}
+
+impl Gate {
+
+    pub fn new() -> Gate {
+        Gate { tolerance: 0.10, alpha: 0.01 }
+    }
+}
+
+impl Default for Gate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Check {
+    pub metric: &'static str,
+    pub baseline: f64,
+    pub current: f64,
+    // None for metrics compared exactly.
+    pub p_value: Option<f64>,
+    pub regressed: bool,
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let change = if self.baseline == 0.0 { 0.0 } else { (self.current / self.baseline - 1.0) * 100.0 };
+        write!(f, "{:<24} {:>12.3} -> {:>12.3} ({:+.1}%)", self.metric, self.baseline, self.current, change)?;
+        if let Some(p) = self.p_value {
+            write!(f, " p = {:.4}", p)?;
+        }
+        write!(f, " {}", if self.regressed { "REGRESSED" } else { "ok" })
+    }
+}
+
+#[derive(Debug)]
+pub enum BaselineError {
+    Io(io::Error),
+    Format(serde_json::Error),
+}
+
+impl fmt::Display for BaselineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BaselineError::Io(e) => write!(f, "cannot read baseline: {}", e),
+            BaselineError::Format(e) => write!(f, "malformed baseline: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for BaselineError {}
+
+pub fn load_baseline(path: &str) -> Result<Results, BaselineError> {
+    let file = File::open(path).map_err(BaselineError::Io)?;
+    serde_json::from_reader(BufReader::new(file)).map_err(BaselineError::Format)
+}
+
+// Upper tail of the standard normal, erfc by Abramowitz and Stegun 7.1.26 (error below 1.5e-7).
+pub fn normal_sf(z: f64) -> f64 {
+    let x = z.abs() / std::f64::consts::SQRT_2;
+    let t = 1.0 / (1.0 + 0.327_591_1 * x);
+    let poly = t * (0.254_829_592 + t * (-0.284_496_736 + t * (1.421_413_741 + t * (-1.453_152_027 + t * 1.061_405_429))));
+    let erfc = poly * (-x * x).exp();
+    if z >= 0.0 { 0.5 * erfc } else { 1.0 - 0.5 * erfc }
+}
+
+// Skewness and kurtosis of samples, those of a normal distribution when there is no spread.
+pub fn shape(samples: &[u64]) -> (f64, f64) {
+    let n = samples.len() as f64;
+    let mean = samples.iter().map(|x| *x as f64).sum::<f64>() / n;
+    let moment = |k| samples.iter().map(|x| (*x as f64 - mean).powi(k)).sum::<f64>() / n;
+    let variance = moment(2);
+    if samples.is_empty() || variance == 0.0 {
+        return (0.0, 3.0);
+    }
+    (moment(3) / variance.powf(1.5), moment(4) / variance.powi(2))
+}
+
+// Variance of a score estimated from a sample. The mean and sd estimates are correlated through the skewness.
+fn score_variance(score: &Score, skewness: f64, kurtosis: f64) -> f64 {
+    let factor = (1.0 + skewness + (kurtosis - 1.0) / 4.0).max(0.0);
+    0.25 * score.sd.powi(2) / score.samples as f64 * factor
+}
+
+// One sided p value that current's score is above baseline's, batches giving the shape of both.
+pub fn score_test(baseline: &Score, current: &Score, batches: &[u64]) -> f64 {
+    if baseline.samples == 0 || current.samples == 0 {
+        return 1.0;
+    }
+    let (skewness, kurtosis) = shape(batches);
+    let se = (score_variance(baseline, skewness, kurtosis) + score_variance(current, skewness, kurtosis)).sqrt();
+    if se == 0.0 {
+        return if current.score > baseline.score { 0.0 } else { 1.0 };
+    }
+    normal_sf((current.score - baseline.score) / se)
+}
+
+// One sided p value that more than share of the samples lie above threshold.
+pub fn exceedance(samples: &[u64], threshold: f64, share: f64) -> f64 {
+    if samples.is_empty() {
+        return 1.0;
+    }
+    let n = samples.len() as f64;
+    let above = samples.iter().filter(|x| **x as f64 > threshold).count() as f64;
+    normal_sf((above - n * share) / (n * share * (1.0 - share)).sqrt())
+}
+
+// What differs between the machines two reports were measured on, for warnings.
+pub fn environment_differences(baseline: &Environment, current: &Environment) -> Vec<String> {
+    let unknown = |x: &Option<String>| x.clone().unwrap_or_else(|| String::from("unknown"));
+    let mut differences = Vec::new();
+    if baseline.cpu_model != current.cpu_model {
+        differences.push(format!("cpu {} in the baseline, {} now", unknown(&baseline.cpu_model), unknown(&current.cpu_model)));
+    }
+    if baseline.governor != current.governor {
+        differences.push(format!("governor {} in the baseline, {} now", unknown(&baseline.governor), unknown(&current.governor)));
+    }
+    differences
+}
+
+// Compares a run, with its batch latencies, to the baseline. Allocations are only compared when both counted them.
+pub fn compare(baseline: &Results, current: &Results, batches: &[u64], gate: &Gate) -> Vec<Check> {
+    let worse = |old: f64, new: f64| new > old * (1.0 + gate.tolerance);
+    let mut checks = Vec::new();
+
+    let p = score_test(&baseline.score, &current.score, batches);
+    checks.push(Check {
+        metric: "score",
+        baseline: baseline.score.score,
+        current: current.score.score,
+        p_value: Some(p),
+        regressed: worse(baseline.score.score, current.score.score) && p < gate.alpha,
+    });
+
+    let threshold = baseline.batch_latency.p99 as f64 * (1.0 + gate.tolerance);
+    let p = exceedance(batches, threshold, 0.01);
+    checks.push(Check {
+        metric: "p99 batch latency",
+        baseline: baseline.batch_latency.p99 as f64,
+        current: current.batch_latency.p99 as f64,
+        p_value: Some(p),
+        regressed: worse(baseline.batch_latency.p99 as f64, current.batch_latency.p99 as f64) && p < gate.alpha,
+    });
+
+    if let (Some(old), Some(new)) = (&baseline.allocations, &current.allocations) {
+        checks.push(Check {
+            metric: "allocations per message",
+            baseline: old.per_message,
+            current: new.per_message,
+            p_value: None,
+            regressed: worse(old.per_message, new.per_message),
+        });
+    }
+
+    checks
+}
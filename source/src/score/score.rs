@@ -1,29 +1,29 @@
 use crate::engine::engine::Engine;
-use crate::types::Order;
+use crate::feed::feed::FlowSource;
+use crate::score::engines::MatchingEngine;
 
 
-fn feed(begin: usize, end: usize, engine: &mut Engine, flow: &mut Vec<Order>) {
+pub(crate) fn feed<E: MatchingEngine, F: FlowSource + ?Sized>(begin: usize, end: usize, engine: &mut E, flow: &F) {
     for idx in begin..end {
-        // Raw simulated order and cancel data feed
-        // orders with price = 0 correspond to 
-        // cancels with orderid=size
-        if flow[idx].price == 0 {
-            engine.cancel(flow[idx].size);
-        }else {
-            engine.limit_order(flow[idx].clone());
-        }
+        engine.submit(flow.command(idx));
     }
 }
 
-pub fn playback(flow: &mut Vec<Order>) {
-    let msg_batch_size = 10;
+pub const MSG_BATCH_SIZE: usize = 10;
 
-    let mut engine = Engine::new();
+pub fn playback<F: FlowSource + ?Sized>(flow: &F) {
+    playback_engine::<Engine, F>(flow)
+}
+
+pub fn playback_engine<E: MatchingEngine, F: FlowSource + ?Sized>(flow: &F) {
+    let msg_batch_size = MSG_BATCH_SIZE;
+
+    let mut engine = E::create();
     
     let mut batch = msg_batch_size;
-    while batch < flow.len() {
+    while batch < flow.messages() {
         feed(batch - msg_batch_size, batch, &mut engine, flow);
 
         batch += msg_batch_size;
     }
-}
\ No newline at end of file
+}
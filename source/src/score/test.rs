@@ -0,0 +1,331 @@
+#[cfg(test)]
+mod score_tests {
+
+    use crate::feed::feed::get_raw_feed;
+    use crate::score::score::MSG_BATCH_SIZE;
+    use crate::score::latency::{measure, score, Score};
+    use crate::score::histogram::{Histogram, SUB_BUCKETS};
+    use crate::score::engines::MatchingEngine;
+    use crate::score::score::playback_engine;
+    use crate::engine::{baseline, engine, winning};
+    use crate::feed::feed::to_command;
+    use crate::score::profiles::{profile, profiles, PROFILE_MESSAGES};
+    use crate::engine::command::Command;
+    use crate::engine::bands::PriceBands;
+    use crate::score::allocations::{allocations, count_engine, CountingAllocator};
+    use std::alloc::{GlobalAlloc, Layout};
+    use crate::score::environment::{affinity, cpu_list, cpu_mask, environment, pin_to_cpu, request_realtime, Environment};
+    use crate::score::cache::{parse_size, CacheEvictor};
+    use crate::score::latency::measure_between;
+    use crate::score::outliers::{outliers, Outlier, Recurring};
+    use crate::score::operations::{breakdown, bucket, classify, Operation};
+    use crate::types::Order;
+    use crate::score::regression::{compare, environment_differences, exceedance, load_baseline, normal_sf, score_test, shape, Gate, Results};
+
+    #[test]
+    fn test_score_formula() {
+        assert_eq!(score(&[2, 4, 4, 4, 5, 5, 7, 9]), Score { samples: 8, mean: 5.0, sd: 2.0, score: 3.5 });
+        assert_eq!(score(&[]).samples, 0);
+    }
+
+    #[test]
+    fn test_batches_match_reference() {
+        let flow = get_raw_feed().unwrap();
+
+        // score.cpp times batches starting at 10, 20, ... below the feed length, 3575 for the score feed.
+        let latencies = measure(flow.as_slice(), 2, MSG_BATCH_SIZE);
+        assert_eq!(latencies.len(), 2 * 3575);
+        assert_eq!(measure(&flow[..21], 1, MSG_BATCH_SIZE).len(), 2);
+        assert_eq!(measure(&flow[..20], 1, MSG_BATCH_SIZE).len(), 1);
+        assert!(score(&latencies).mean > 0.0);
+    }
+
+    #[test]
+    fn test_histogram_percentiles() {
+        let histogram = Histogram::from_samples(&(1..=10_000).collect::<Vec<u64>>());
+        let summary = histogram.summary();
+        assert_eq!((summary.count, summary.max), (10_000, 10_000));
+        assert_eq!(histogram.mean(), 5000.5);
+
+        // Reported values are bucket upper bounds, within one sub bucket of the exact percentile.
+        for (reported, exact) in &[(summary.p50, 5000), (summary.p90, 9000), (summary.p99, 9900), (summary.p999, 9990)] {
+            assert!(*reported >= *exact && *reported as f64 <= *exact as f64 * (1.0 + 1.0 / SUB_BUCKETS as f64), "{} {}", reported, exact);
+        }
+
+        // Small values are exact.
+        let small = Histogram::from_samples(&[3, 1, 2, 2, 31]);
+        assert_eq!((small.min(), small.percentile(50.0), small.percentile(80.0), small.percentile(100.0)), (1, 2, 3, 31));
+        assert_eq!(Histogram::new().summary().p99, 0);
+
+        let huge = Histogram::from_samples(&[u64::MAX, 1 << 40]);
+        assert_eq!((huge.percentile(50.0), huge.percentile(100.0)), ((1 << 40) + (1 << 36) - 1, u64::MAX));
+    }
+
+    #[test]
+    fn test_histogram_render() {
+        let histogram = Histogram::from_samples(&[1, 5, 6, 7, 100, 100]);
+        let rendered = histogram.render(10);
+        let rows: Vec<&str> = rendered.lines().collect();
+
+        // Rows 1, 2-3, 4-7, 8-15, 16-31, 32-63 and 64-127.
+        assert_eq!(rows.len(), 7);
+        assert_eq!(rows[0], "           1 - 1            |###       | 1");
+        assert_eq!(rows[2], "           4 - 7            |##########| 3");
+        assert_eq!(rows[3], "           8 - 15           |          | 0");
+        assert!(rows[6].ends_with("|#######   | 2"));
+    }
+
+    #[test]
+    fn test_engines_replay_identical_input() {
+        let flow = get_raw_feed().unwrap();
+
+        assert_eq!((engine::Engine::NAME, baseline::Engine::NAME, winning::Engine::NAME), ("current", "baseline", "winning"));
+        playback_engine::<engine::Engine, _>(flow.as_slice());
+        playback_engine::<baseline::Engine, _>(flow.as_slice());
+        playback_engine::<winning::Engine, _>(flow.as_slice());
+
+        // The example engines only understand limit orders and cancels.
+        let mut winner = winning::Engine::new_debug();
+        for order in &flow[..100] {
+            winner.submit(to_command(order));
+        }
+        assert!(!winner.execution_log.is_empty());
+    }
+
+    #[test]
+    fn test_profiles_stress_their_path() {
+        let names: Vec<&str> = profiles(1).iter().map(|x| x.name).collect();
+        assert_eq!(names, ["deep_book", "cancel_heavy", "sweeps", "one_price", "sparse_levels"]);
+        assert!(profile("missing", 1).is_none());
+
+        let limit_prices = |name: &str| {
+            let mut prices: Vec<u16> = profile(name, 1).unwrap().commands().iter()
+                .filter_map(|x| if let Command::Limit(order) = x { Some(order.price) } else { None }).collect();
+            prices.sort_unstable();
+            prices.dedup();
+            prices
+        };
+        assert!(limit_prices("deep_book").len() > 2000);
+        assert_eq!(limit_prices("one_price").len(), 2);
+        assert!(limit_prices("sparse_levels").iter().all(|x| (*x as i32 - 32_768) % 500 == 0));
+
+        let cancels = profile("cancel_heavy", 1).unwrap().commands().iter().filter(|x| matches!(x, Command::Cancel(_))).count();
+        assert!(cancels * 100 / PROFILE_MESSAGES >= 40);
+
+        // Some sweeps fill dozens of resting orders in one go.
+        let mut current = engine::Engine::new_debug();
+        let mut widest = 0;
+        for command in profile("sweeps", 1).unwrap().commands() {
+            let before = current.execution_log.len();
+            current.apply(command);
+            widest = widest.max((current.execution_log.len() - before) / 2);
+        }
+        assert!(widest >= 30, "{}", widest);
+
+        for profile in profiles(1) {
+            let commands = profile.commands();
+            playback_engine::<baseline::Engine, _>(commands.as_slice());
+            playback_engine::<winning::Engine, _>(commands.as_slice());
+        }
+    }
+
+    #[test]
+    fn test_counting_allocator() {
+        // Not installed in the test binary, so only these calls are counted.
+        let before = allocations();
+        unsafe {
+            let layout = Layout::from_size_align(64, 8).unwrap();
+            let ptr = CountingAllocator.alloc(layout);
+            let ptr = CountingAllocator.realloc(ptr, layout, 256);
+            CountingAllocator.dealloc(ptr, Layout::from_size_align(256, 8).unwrap());
+        }
+        let used = allocations().since(before);
+        assert_eq!((used.count, used.bytes), (2, 320));
+
+        // Batches line up with the timed runs. What is counted is checked in tests/allocations.rs,
+        // which installs the allocator.
+        let flow = get_raw_feed().unwrap();
+        let report = count_engine::<engine::Engine, _>(flow.as_slice(), MSG_BATCH_SIZE);
+        assert_eq!((report.batches, report.messages), (3575, 35750));
+    }
+
+    #[test]
+    fn test_statistical_tests() {
+        assert!((normal_sf(0.0) - 0.5).abs() < 1e-7);
+        assert!((normal_sf(1.959_964) - 0.025).abs() < 1e-6);
+        assert!((normal_sf(-1.959_964) - 0.975).abs() < 1e-6);
+
+        // A score is tested whether the mean or the sd moved it.
+        let before = Score { samples: 10_000, mean: 100.0, sd: 50.0, score: 75.0 };
+        assert!(score_test(&before, &Score { mean: 106.0, score: 78.0, ..before }, &[]) < 0.001);
+        assert!(score_test(&before, &Score { sd: 56.0, score: 78.0, ..before }, &[]) < 0.001);
+        assert!(score_test(&before, &Score { mean: 100.5, score: 75.25, ..before }, &[]) > 0.2);
+        assert!(score_test(&before, &Score { mean: 90.0, score: 70.0, ..before }, &[]) > 0.99);
+
+        // A long tail makes the sd, and so the score, harder to pin down.
+        assert_eq!(shape(&[5, 5, 5]), (0.0, 3.0));
+        let tailed: Vec<u64> = (0..10_000).map(|x| if x % 100 == 0 { 1000 } else { 100 }).collect();
+        assert!(shape(&tailed).1 > 50.0);
+        let shifted = Score { sd: 53.0, score: 76.5, ..before };
+        assert!(score_test(&before, &shifted, &tailed) > score_test(&before, &shifted, &[]));
+
+        // 1% of 10000 samples above the threshold is expected, 2% is not.
+        let samples: Vec<u64> = (0..10_000).map(|x| if x < 200 { 1000 } else { 10 }).collect();
+        assert!(exceedance(&samples, 500.0, 0.01) < 0.001);
+        assert!(exceedance(&samples[100..], 500.0, 0.01) > 0.4);
+    }
+
+    #[test]
+    fn test_regression_gate() {
+        // Baselines are plain --format json reports, extra fields are ignored.
+        let baseline: Results = serde_json::from_str(r#"{"engine": "current", "feed": "data/score_feed.csv", "messages": 35759,
+            "batch_size": 10, "replays": 200, "warmup": 0, "samples": 10000, "mean": 1000.0, "sd": 400.0, "score": 700.0,
+            "batch_latency": {"count": 10000, "p50": 900, "p90": 1500, "p99": 2000, "p999": 3000, "max": 5000},
+            "message_latency": null, "allocations": {"messages": 35750, "batches": 3575, "total": {"count": 0, "bytes": 0},
+            "per_message": 0.0, "bytes_per_message": 0.0, "per_batch": 0.0, "bytes_per_batch": 0.0, "max_per_batch": 0,
+            "allocating_messages": 0}}"#).unwrap();
+        let gate = Gate::new();
+        let quiet: Vec<u64> = vec![1000; 10_000];
+
+        let checks = compare(&baseline, &baseline, &quiet, &gate);
+        assert_eq!(checks.iter().map(|x| x.metric).collect::<Vec<_>>(), ["score", "p99 batch latency", "allocations per message"]);
+        assert!(checks.iter().all(|x| !x.regressed));
+
+        // Slower on average and in the tail.
+        let mut slower = baseline.clone();
+        slower.score = Score { mean: 1200.0, score: 800.0, ..baseline.score };
+        slower.batch_latency.p99 = 3000;
+        let tail: Vec<u64> = (0..10_000).map(|x| if x < 500 { 3000 } else { 1000 }).collect();
+        let checks = compare(&baseline, &slower, &tail, &gate);
+        assert!(checks[0].regressed && checks[1].regressed && !checks[2].regressed);
+
+        // Within tolerance passes, a worse score from the sd alone does not.
+        let mut noisy = baseline.clone();
+        noisy.score = Score { mean: 1050.0, score: 735.0, ..baseline.score };
+        assert!(!compare(&baseline, &noisy, &quiet, &gate)[0].regressed);
+        noisy.score = Score { mean: 1000.0, sd: 800.0, score: 900.0, ..baseline.score };
+        assert!(compare(&baseline, &noisy, &quiet, &gate)[0].regressed);
+
+        // An allocation free baseline tolerates no allocations, and only counted runs compare them.
+        let mut allocating = baseline.clone();
+        allocating.allocations.as_mut().unwrap().per_message = 0.001;
+        assert!(compare(&baseline, &allocating, &quiet, &gate)[2].regressed);
+        allocating.allocations = None;
+        assert_eq!(compare(&baseline, &allocating, &quiet, &gate).len(), 2);
+
+        assert_eq!(load_baseline("data/baseline_results.json").unwrap().batch_size, MSG_BATCH_SIZE);
+        assert_eq!(baseline.environment, None);
+    }
+
+    #[test]
+    fn test_environment_differences() {
+        let here = Environment { cpu_model: Some(String::from("A")), governor: Some(String::from("performance")), ..Environment::default() };
+        assert!(environment_differences(&here, &Environment { kernel: Some(String::from("6.1")), ..here.clone() }).is_empty());
+
+        let there = Environment { cpu_model: Some(String::from("B")), governor: None, ..here.clone() };
+        assert_eq!(environment_differences(&here, &there), ["cpu A in the baseline, B now", "governor performance in the baseline, unknown now"]);
+    }
+
+    #[test]
+    fn test_environment() {
+        assert_eq!(cpu_list(&[0, 1, 2, 3, 6, 8, 9]), "0-3,6,8-9");
+        assert_eq!(cpu_list(&[]), "");
+        assert_eq!((cpu_mask(&[1]), cpu_mask(&[0, 2, 3]), cpu_mask(&[0, 33])), ("00000002".to_string(), "0000000d".to_string(), "00000002,00000001".to_string()));
+        assert!(request_realtime(0).is_err() && request_realtime(100).is_err());
+
+        // Pinning only affects this test's thread.
+        if cfg!(target_os = "linux") {
+            let allowed = affinity().unwrap();
+            pin_to_cpu(allowed[0]).unwrap();
+            assert_eq!(affinity().unwrap(), [allowed[0]]);
+            assert_eq!(environment().affinity, [allowed[0]]);
+            assert!(pin_to_cpu(1 << 20).is_err());
+        }
+    }
+
+    #[test]
+    fn test_outliers_keep_their_offsets() {
+        // Three replays of a 41 message feed in batches of 10, four batches each.
+        let mut latencies = vec![100u64; 12];
+        latencies[1] = 5000;
+        latencies[5] = 4000;
+        latencies[11] = 9000;
+        latencies[3] = 120;
+
+        let report = outliers(&latencies, 41, 10, 10);
+        assert_eq!((report.count, report.median, report.threshold), (3, 100, 120 + 3 * 20));
+        assert_eq!(report.worst, [
+            Outlier { replay: 2, offset: 30, latency: 9000 },
+            Outlier { replay: 0, offset: 10, latency: 5000 },
+            Outlier { replay: 1, offset: 10, latency: 4000 },
+        ]);
+        assert_eq!(report.recurring, [Recurring { offset: 10, replays: 2 }]);
+
+        assert_eq!(outliers(&latencies, 41, 10, 1).worst.len(), 1);
+        assert_eq!(outliers(&[], 41, 10, 10).count, 0);
+    }
+
+    #[test]
+    fn test_cold_replays() {
+        assert_eq!((parse_size("32K"), parse_size("8M"), parse_size("512"), parse_size("")), (Some(32 << 10), Some(8 << 20), Some(512), None));
+
+        let mut evictor = CacheEvictor::with_size(1 << 16);
+        evictor.evict();
+        assert_eq!(evictor.size(), 1 << 16);
+
+        // Caches are evicted once per replay, before any of it is timed.
+        let flow = get_raw_feed().unwrap();
+        let mut evictions = 0;
+        let latencies = measure_between::<engine::Engine, _, _>(&flow[..101], 3, MSG_BATCH_SIZE, || {
+            evictor.evict();
+            evictions += 1;
+        });
+        assert_eq!((evictions, latencies.len()), (3, 30));
+    }
+
+    #[test]
+    fn test_operation_breakdown() {
+        let order = |side: u8, price: u16, size: u64| Order { symbol: String::from("S"), trader: String::from("T"), side, price, size };
+        let flow = vec![
+            Command::Limit(order(1, 10, 1)),
+            Command::Limit(order(1, 10, 1)),
+            Command::Limit(order(1, 11, 1)),
+            Command::Limit(order(1, 12, 5)),
+            // Takes both orders at 10 and the one at 11.
+            Command::Limit(order(0, 11, 3)),
+            Command::Cancel(1),
+            Command::Cancel(4),
+            Command::Limit(order(0, 5, 1)),
+            // Trades with the bid at 5.
+            Command::Market(order(1, 0, 1)),
+        ];
+        let operations = classify(flow.as_slice());
+        assert_eq!(operations, [
+            Operation::Resting, Operation::Resting, Operation::Resting, Operation::Resting,
+            Operation::Crossing { levels: 2, orders: 2 },
+            Operation::CancelMiss, Operation::CancelHit, Operation::Resting, Operation::Crossing { levels: 1, orders: 1 },
+        ]);
+        assert_eq!((bucket(0), bucket(1), bucket(3), bucket(4), bucket(100)), (0, 1, 2, 4, 64));
+        assert_eq!(operations[4].to_string(), "crossing levels 2-3 orders 2-3");
+
+        // Two replays of the eight timed messages.
+        let latencies: Vec<u64> = vec![10, 10, 10, 10, 100, 5, 20, 1, 30, 30, 30, 30, 300, 5, 40, 1];
+        let split = breakdown(&operations, &latencies);
+        assert_eq!(split.iter().map(|x| x.operation).collect::<Vec<_>>(), [
+            Operation::Resting, Operation::Crossing { levels: 2, orders: 2 }, Operation::CancelHit, Operation::CancelMiss,
+        ]);
+        assert_eq!((split[0].latency.count, split[0].mean, split[1].latency.max), (10, 16.2, 300));
+        assert!((split[1].share - 400.0 / 632.0).abs() < 1e-9);
+        assert!((split.iter().map(|x| x.share).sum::<f64>() - 1.0).abs() < 1e-9);
+
+        // Rejected orders are told apart from the rest, a market order that finds nothing is not a cross.
+        let flow = vec![
+            Command::SetReferencePrice(Some(10)),
+            Command::SetPriceBands(PriceBands { static_band: Some(2), dynamic_band: None }),
+            Command::Limit(order(0, 20, 1)),
+            Command::Market(order(0, 0, 1)),
+        ];
+        assert_eq!(classify(flow.as_slice()), [Operation::Other, Operation::Other, Operation::Rejected, Operation::Other]);
+        assert_eq!(Operation::Rejected.to_string(), "rejected order");
+    }
+}
@@ -1,18 +1,40 @@
-use criterion::{criterion_group, criterion_main, Criterion};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
 
-mod types;
-mod engine;
-mod feed;
-mod score;
+use high_frequency_order_book::engine::{baseline, engine, winning};
+use high_frequency_order_book::score::engines::MatchingEngine;
+use high_frequency_order_book::score::score::playback_engine;
+use high_frequency_order_book::score::profiles::profiles;
+use high_frequency_order_book::feed::feed::{get_raw_feed, FlowSource};
 
-use crate::score::score::playback;
-use crate::feed::feed::get_raw_feed;
+// Seed of the profile feeds, fixed so results stay comparable between runs.
+const PROFILE_SEED: u64 = 1;
 
+fn bench_engine<E: MatchingEngine, F: FlowSource + ?Sized>(group: &mut criterion::BenchmarkGroup<criterion::measurement::WallTime>, flow: &F) {
+    group.bench_function(E::NAME, |b| b.iter(|| playback_engine::<E, F>(flow)));
+}
+
+// Every engine replays the same score feed and then each profile, throughput is in messages per second.
 fn criterion_benchmark(c: &mut Criterion) {
-    let mut flow = get_raw_feed();
+    let flow = get_raw_feed().expect("score feed");
+
+    let mut group = c.benchmark_group("score");
+    group.throughput(Throughput::Elements(flow.len() as u64));
+    bench_engine::<engine::Engine, _>(&mut group, flow.as_slice());
+    bench_engine::<baseline::Engine, _>(&mut group, flow.as_slice());
+    bench_engine::<winning::Engine, _>(&mut group, flow.as_slice());
+    group.finish();
 
-    c.bench_function("score", |b| b.iter(|| playback(&mut flow)));
+    // One group per profile, so each engine path is reported on its own.
+    for profile in profiles(PROFILE_SEED) {
+        let commands = profile.commands();
+        let mut group = c.benchmark_group(format!("profile/{}", profile.name));
+        group.throughput(Throughput::Elements(commands.len() as u64));
+        bench_engine::<engine::Engine, _>(&mut group, commands.as_slice());
+        bench_engine::<baseline::Engine, _>(&mut group, commands.as_slice());
+        bench_engine::<winning::Engine, _>(&mut group, commands.as_slice());
+        group.finish();
+    }
 }
 
 criterion_group!(benches, criterion_benchmark);
-criterion_main!(benches);
\ No newline at end of file
+criterion_main!(benches);
@@ -8,7 +8,7 @@ pub type Price = u16;
 pub type Size = u64;
 
 pub type Side = u8;
-pub fn is_ask(s: Side) -> bool { return s == 1; }
+pub fn is_ask(s: Side) -> bool { s == 1 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Order {
@@ -22,11 +22,11 @@ pub struct Order {
 
 impl PartialEq for Order {
     fn eq(&self, other: &Self) -> bool {
-        return self.symbol == other.symbol &&
-                self.trader == other.trader &&
-                self.side == other.side &&
-                self.price == other.price &&
-                self.size == other.size;
+        self.symbol == other.symbol &&
+            self.trader == other.trader &&
+            self.side == other.side &&
+            self.price == other.price &&
+            self.size == other.size
     }
 }
 
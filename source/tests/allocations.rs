@@ -0,0 +1,31 @@
+/*
+    CountingAllocator installed as the global allocator, as the scoring CLI does with count-allocations.
+    The library's unit tests run without it, so only here are the engine's own allocations counted.
+*/
+
+use high_frequency_order_book::engine::command::Command;
+use high_frequency_order_book::engine::engine::Engine;
+use high_frequency_order_book::score::allocations::{allocations, count_engine, CountingAllocator};
+use high_frequency_order_book::types::Order;
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn limit(trader: &str, side: u8, price: u16, size: u64) -> Command {
+    Command::Limit(Order { symbol: String::from("SYM"), trader: String::from(trader), side, price, size })
+}
+
+#[test]
+fn test_allocations_are_counted() {
+    let before = allocations();
+    std::hint::black_box(Box::new([0u8; 100]));
+    assert!(allocations().since(before).count >= 1);
+
+    // The first order to rest grows an empty book, so it allocates.
+    let commands = vec![limit("ID1", 0, 100, 10), limit("ID2", 1, 101, 10), limit("ID3", 0, 99, 10)];
+    let report = count_engine::<Engine, _>(commands.as_slice(), 1);
+    assert_eq!((report.batches, report.messages), (2, 2));
+    assert!(report.total.count > 0 && report.total.bytes > 0);
+    assert!(report.allocating_messages > 0);
+    assert!(report.per_message > 0.0);
+}